@@ -0,0 +1,38 @@
+use clap::Parser;
+use log::info;
+use std::fs::File;
+use zkevm::prover::AggCircuitProof;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// the path of agg proof, e.g. `full_proof.data`.
+    #[clap(long = "agg")]
+    agg_proof: String,
+    /// Write the calldata to the file instead of printing it as hex.
+    #[clap(short, long)]
+    output: Option<String>,
+    /// Encode only the arguments, without the function selector.
+    #[clap(long)]
+    no_selector: bool,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args = Args::parse();
+    let file = File::open(&args.agg_proof).unwrap();
+    let proof: AggCircuitProof = serde_json::from_reader(file).unwrap();
+    let calldata = proof
+        .verifier_calldata(!args.no_selector)
+        .expect("failed to encode calldata");
+
+    match args.output {
+        Some(path) => {
+            std::fs::write(&path, &calldata).unwrap();
+            info!("write {} bytes of calldata to {}", calldata.len(), path);
+        }
+        None => println!("0x{}", hex::encode(&calldata)),
+    }
+}
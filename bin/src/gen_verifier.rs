@@ -0,0 +1,33 @@
+use clap::Parser;
+use log::info;
+use zkevm::circuit::AGG_DEGREE;
+use zkevm::solidity::generate_verifier_from_vks;
+use zkevm::utils::load_kzg_params;
+use zkevm::vk_registry::read_vk_dir;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Specify directory which params have stored in. (default: ./kzg_params)
+    #[clap(default_value = "./kzg_params", short, long)]
+    params_dir: String,
+    /// Get the target and agg vks from the directory, e.g. the debug dir of the prover.
+    #[clap(long = "vk-dir")]
+    vk_dir: String,
+    /// Write the verifier contract to the file. (default: ./verifier.sol)
+    #[clap(default_value = "./verifier.sol", short, long)]
+    output: String,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args = Args::parse();
+    let agg_params =
+        load_kzg_params(&args.params_dir, *AGG_DEGREE).expect("failed to load kzg params");
+    let vks = read_vk_dir(&args.vk_dir).expect("failed to read vks");
+    let sol = generate_verifier_from_vks(&agg_params, &vks).expect("failed to generate verifier");
+    std::fs::write(&args.output, sol).unwrap();
+    info!("verifier solidity to {}", args.output);
+}
@@ -4,12 +4,13 @@ use itertools::Itertools;
 use reqwest::Url;
 use serde::Deserialize;
 use std::env;
+use std::sync::Arc;
 use types::eth::BlockTrace;
 use zkevm::circuit::{
-    block_traces_to_witness_block, calculate_row_usage_of_witness_block, SuperCircuit,
+    block_traces_to_witness_block, calculate_row_usage_of_witness_block, CIRCUIT_PROFILE,
     SUB_CIRCUIT_NAMES,
 };
-use zkevm::prover::Prover;
+use zkevm::prover::{LogObserver, Prover, ProverObserver};
 
 const DEFAULT_BEGIN_BATCH: i64 = 1;
 const DEFAULT_END_BATCH: i64 = i64::MAX;
@@ -26,6 +27,7 @@ async fn main() {
 
     let provider = Provider::<Http>::try_from(&setting.l2geth_api_url)
         .expect("mock-testnet: failed to initialize ethers Provider");
+    let observer: Arc<dyn ProverObserver> = Arc::new(LogObserver);
 
     for i in setting.begin_batch..=setting.end_batch {
         log::info!("move-testnet: requesting block traces of batch {i}");
@@ -67,7 +69,10 @@ async fn main() {
                     );
                     Ok(())
                 } else {
-                    Prover::mock_prove_target_circuit_batch::<SuperCircuit>(&block_traces)
+                    zkevm::with_super_circuit!(
+                        *CIRCUIT_PROFILE,
+                        C => Prover::mock_prove_target_circuit_batch::<C>(&block_traces, &observer)
+                    )
                 }
             })();
             match result {
@@ -0,0 +1,74 @@
+use clap::Parser;
+use log::info;
+use std::path::PathBuf;
+use types::eth::BlockTrace;
+use zkevm::circuit::{
+    plan_batches, plan_batches_estimated, ChunkLimit, CIRCUIT_PROFILE, ESTIMATE_ERROR_BOUND,
+};
+use zkevm::utils::get_block_trace_from_file;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Specify directory of block traces, planned in the order of their block numbers.
+    #[clap(short, long)]
+    trace_path: String,
+    /// Print the plan as json.
+    #[clap(long)]
+    json: bool,
+    /// Plan with estimated row usage instead of building the witness of every block.
+    #[clap(long)]
+    estimate: bool,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args = Args::parse();
+    let mut traces: Vec<BlockTrace> = std::fs::read_dir(PathBuf::from(&args.trace_path))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file() && path.extension().map_or(false, |e| e == "json"))
+        .map(get_block_trace_from_file)
+        .collect();
+    traces.sort_by_key(|trace| trace.header.number.map(|n| n.as_u64()));
+
+    info!("plan {} blocks with {}", traces.len(), *CIRCUIT_PROFILE);
+    let plan = if args.estimate {
+        info!(
+            "row usage is estimated, {}% of every chunk is left for the estimation error",
+            ESTIMATE_ERROR_BOUND * 100.0
+        );
+        plan_batches_estimated(&traces)
+    } else {
+        plan_batches(&traces)
+    }
+    .expect("failed to plan batches");
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&plan).unwrap());
+        return;
+    }
+
+    let block_number = |idx: usize| traces[idx].header.number.map(|n| n.as_u64());
+    for chunk in plan.chunks.iter() {
+        let (bottleneck, rows) = chunk.bottleneck();
+        let reason = match &chunk.limit {
+            ChunkLimit::End => "end of blocks".to_string(),
+            ChunkLimit::Rows { circuit, rows } => {
+                format!("next block needs {rows} rows of {circuit}")
+            }
+            ChunkLimit::Blocks => format!("{} blocks at most", plan.max_blocks),
+        };
+        println!(
+            "blocks {:?}..={:?}: {} of {} rows ({:.1}%) in {}, closed by {}",
+            block_number(chunk.blocks.start),
+            block_number(chunk.blocks.end - 1),
+            rows,
+            plan.max_rows,
+            chunk.utilization(plan.max_rows) * 100.0,
+            bottleneck,
+            reason
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{ArgEnum, Parser};
+use clap::Parser;
 use halo2_proofs::{consts::SEED, halo2curves::bn256::Bn256, poly::kzg::commitment::ParamsKZG};
 use std::{
     collections::HashMap,
@@ -10,19 +10,14 @@ use std::{
 use types::eth::BlockTrace;
 use utils::{check_chain_id, is_tachyon, Measurer};
 use zkevm::{
-    circuit::{EvmCircuit, StateCircuit, AGG_DEGREE, DEGREE, MAX_TXS},
+    circuit::{target_circuit, target_circuit_names, AGG_DEGREE, CIRCUIT_PROFILE, DEGREE},
     io::write_file,
-    prover::Prover,
+    prover::{Prover, TranscriptKind},
     utils::{get_block_trace_from_file, load_kzg_params},
 };
 
-#[derive(ArgEnum, Debug, Clone, PartialEq)]
-#[clap(rename_all = "kebab_case")]
-enum CircuitType {
-    EVM,
-    STATE,
-    AGG,
-}
+/// Name of the aggregation circuit in `--circuit`.
+const AGG_CIRCUIT: &str = "agg";
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -33,12 +28,20 @@ struct Args {
     /// Specify path to block trace. (json file or directory)
     #[clap(short, long)]
     trace_path: String,
-    /// Specify circuit type in [evm, state, agg].
-    #[clap(short, long, arg_enum)]
-    circuit: CircuitType,
+    /// Specify circuit by name: a target circuit, e.g. evm, state or super, or agg.
+    /// Run with an unknown name to list them.
+    #[clap(short, long)]
+    circuit: String,
     /// Specify whether to create `Verifier.sol`. (default: true)
     #[clap(default_value_t = true, short, long = "gen_sol")]
     gen_sol: bool,
+    /// Seed the prover with a fixed value instead of OS randomness. (for testing only)
+    #[clap(long)]
+    deterministic: bool,
+    /// Prove the target circuit with a Keccak transcript, so it can be verified on-chain
+    /// without aggregation. The aggregation params are not loaded.
+    #[clap(long)]
+    keccak: bool,
 }
 
 impl Args {
@@ -64,25 +67,24 @@ impl Args {
 
     fn load_params(&self) -> (ParamsKZG<Bn256>, Option<ParamsKZG<Bn256>>) {
         let params = load_kzg_params(&self.params_dir, *DEGREE).expect("failed to load kzg params");
-        let agg_params = match self.circuit {
-            CircuitType::AGG => {
-                let params = load_kzg_params(&self.params_dir, *AGG_DEGREE)
-                    .expect("failed to load kzg agg params");
-                Some(params)
-            }
-            _ => None,
+        let agg_params = if self.circuit == AGG_CIRCUIT {
+            let params = load_kzg_params(&self.params_dir, *AGG_DEGREE)
+                .expect("failed to load kzg agg params");
+            Some(params)
+        } else {
+            None
         };
         (params, agg_params)
     }
 
     fn panic_if_tx_too_many(trace: &BlockTrace) {
         let tx_count = trace.transactions.len();
-        if tx_count > MAX_TXS {
+        if tx_count > CIRCUIT_PROFILE.max_txs {
             panic!(
                 "{}",
                 format!(
                     "too many transactions. MAX_TXS: {}, given transactions: {}",
-                    MAX_TXS, tx_count
+                    CIRCUIT_PROFILE.max_txs, tx_count
                 )
             );
         }
@@ -97,11 +99,29 @@ fn main() {
     let is_tachyon = is_tachyon();
     log::info!("chain_id: {chain_id}, tachyon: {is_tachyon}");
     let args = Args::parse();
+    let circuit = target_circuit(&args.circuit);
+    if circuit.is_none() && args.circuit != AGG_CIRCUIT {
+        panic!(
+            "unknown circuit {}, expected one of {:?} or {}",
+            args.circuit,
+            target_circuit_names(),
+            AGG_CIRCUIT
+        );
+    }
 
     // Prepare KZG params and rng for prover
     let mut timer = Measurer::new();
     let (params, agg_params) = args.load_params();
-    let mut prover = Prover::from_params_and_seed(params, agg_params, SEED);
+    let mut prover = if args.deterministic {
+        Prover::from_params_and_seed(params, agg_params, SEED)
+    } else {
+        Prover::new(params, agg_params)
+    };
+    prover.chain_id = chain_id.parse().unwrap();
+    prover.agg_params_dir = Some(args.params_dir.clone());
+    if args.keccak {
+        prover.target_transcript = TranscriptKind::Keccak;
+    }
     timer.end("finish loading params");
 
     // Getting traces from specific directory
@@ -115,24 +135,22 @@ fn main() {
         prover.debug_dir = String::from(out_dir.to_str().unwrap());
 
         timer.start();
-        match args.circuit {
-            CircuitType::EVM => {
-                let proof_path = PathBuf::from(&trace_name).join("evm.proof");
-                let evm_proof = prover
-                    .create_target_circuit_proof::<EvmCircuit>(&trace)
-                    .expect("cannot generate evm_proof");
-                let mut f = File::create(&proof_path).unwrap();
-                f.write_all(evm_proof.proof.as_slice()).unwrap();
-            }
-            CircuitType::STATE => {
-                let proof_path = PathBuf::from(&trace_name).join("state.proof");
-                let state_proof = prover
-                    .create_target_circuit_proof::<StateCircuit>(&trace)
-                    .expect("cannot generate state_proof");
+        match circuit {
+            Some(circuit) => {
+                let name = circuit.name();
+                let proof_path = PathBuf::from(&trace_name).join(format!("{name}.proof"));
+                let proof = circuit
+                    .prove(&mut prover, std::slice::from_ref(&trace))
+                    .unwrap_or_else(|e| panic!("cannot generate {name}_proof: {e}"));
                 let mut f = File::create(&proof_path).unwrap();
-                f.write_all(state_proof.proof.as_slice()).unwrap();
+                f.write_all(proof.proof.as_slice()).unwrap();
+
+                if args.keccak {
+                    let calldata = proof.evm_calldata().expect("cannot generate calldata");
+                    write_file(&mut out_dir, &format!("{name}_calldata.data"), &calldata);
+                }
             }
-            CircuitType::AGG => {
+            None => {
                 let mut proof_path = PathBuf::from(&trace_name).join("agg.proof");
                 let agg_proof = prover
                     .create_agg_circuit_proof(&trace, args.gen_sol)
@@ -141,7 +159,9 @@ fn main() {
                 agg_proof.write_to_dir(&mut proof_path);
 
                 if args.gen_sol {
-                    let sol = prover.create_solidity_verifier(&agg_proof);
+                    let sol = prover
+                        .create_solidity_verifier(&agg_proof)
+                        .expect("cannot generate solidity verifier");
                     write_file(
                         &mut out_dir,
                         "verifier.sol",
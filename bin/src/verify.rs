@@ -1,9 +1,11 @@
 use clap::Parser;
 use log::info;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use zkevm::prover::{AggCircuitProof, TargetCircuitProof};
 use zkevm::verifier::Verifier;
+use zkevm::vk_registry::VkRegistry;
 use zkevm::{
     circuit::{EvmCircuit, StateCircuit, AGG_DEGREE, DEGREE},
     utils::load_kzg_params,
@@ -18,6 +20,16 @@ struct Args {
     /// Get vk from the file.
     #[clap(long = "vk")]
     vk_path: Option<String>,
+    /// Get target and agg vks from the directory instead of running keygen.
+    #[clap(long = "vk-dir")]
+    vk_dir: Option<String>,
+    /// Json file of the published vk digests by circuit name. Used with `--vk-dir`.
+    #[clap(long = "vk-digests")]
+    vk_digests: Option<String>,
+    /// Get the vk digests of this prover version and circuit config from the vk registry.
+    /// Used with `--vk-dir` if `--vk-digests` is not given.
+    #[clap(long = "registry")]
+    registry_path: Option<String>,
     /// the path of evm proof to verify.
     #[clap(long = "evm")]
     evm_proof: Option<String>,
@@ -34,13 +46,32 @@ fn main() {
     env_logger::init();
 
     let args = Args::parse();
-    let params = load_kzg_params(&args.params_path.clone().unwrap(), *DEGREE)
+    let agg_params = load_kzg_params(&args.params_path.clone().unwrap(), *AGG_DEGREE)
         .expect("failed to load kzg params");
-    let agg_params = load_kzg_params(&args.params_path.unwrap(), *AGG_DEGREE)
-        .expect("failed to load kzg params");
-    let agg_vk = read_from_file(&args.vk_path.unwrap());
 
-    let mut v = Verifier::from_params(params, agg_params, Some(agg_vk));
+    let mut v = match args.vk_dir {
+        Some(vk_dir) => {
+            let digests: HashMap<String, String> = match args.vk_digests {
+                Some(path) => serde_json::from_slice(&read_from_file(&path)).unwrap(),
+                None => {
+                    let registry = VkRegistry::load(args.registry_path.unwrap())
+                        .expect("failed to load vk registry");
+                    registry
+                        .current()
+                        .expect("vks not registered")
+                        .digests
+                        .clone()
+                }
+            };
+            Verifier::from_vk_dir(None, agg_params, vk_dir, &digests).expect("failed to load vks")
+        }
+        None => {
+            let params = load_kzg_params(&args.params_path.unwrap(), *DEGREE)
+                .expect("failed to load kzg params");
+            let agg_vk = read_from_file(&args.vk_path.unwrap());
+            Verifier::from_params(params, agg_params, Some(agg_vk))
+        }
+    };
     if let Some(path) = args.evm_proof {
         let proof_vec = read_from_file(&path);
         let proof = serde_json::from_slice::<TargetCircuitProof>(proof_vec.as_slice()).unwrap();
@@ -0,0 +1,44 @@
+use clap::Parser;
+use log::info;
+use zkevm::version;
+use zkevm::vk_registry::{read_vk_dir, CircuitConfig, VkRegistry, VK_REGISTRY_PATH};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Get vks from the directory, e.g. the debug dir of the prover.
+    #[clap(long = "vk-dir")]
+    vk_dir: String,
+    /// Register the vks into the registry file. Defaults to `VK_REGISTRY_PATH`.
+    #[clap(long = "registry")]
+    registry_path: Option<String>,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args = Args::parse();
+    let registry_path = args
+        .registry_path
+        .unwrap_or_else(|| VK_REGISTRY_PATH.clone());
+    let mut registry = if std::path::Path::new(&registry_path).exists() {
+        VkRegistry::load(&registry_path).expect("failed to load vk registry")
+    } else {
+        VkRegistry::default()
+    };
+
+    let vks = read_vk_dir(&args.vk_dir).expect("failed to read vks");
+    let prover_version = version::as_string();
+    let config = CircuitConfig::current();
+    registry.register(&prover_version, config.clone(), &vks);
+    registry
+        .save(&registry_path)
+        .expect("failed to save vk registry");
+
+    let entry = registry.get(&prover_version, &config).unwrap();
+    for (name, digest) in entry.digests.iter() {
+        info!("registered vk of {} circuit: {}", name, digest);
+    }
+    info!("vk registry written to {}", registry_path);
+}
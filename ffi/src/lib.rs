@@ -1,3 +1,4 @@
+mod observer;
 pub mod prove;
 pub mod verify;
 
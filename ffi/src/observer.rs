@@ -0,0 +1,77 @@
+use libc::c_char;
+use serde_json::json;
+use std::ffi::CString;
+use std::time::Duration;
+use zkevm::prover::{ProverObserver, ProverPhase};
+
+/// Receives the progress of the prover as a json object. The string is only valid during
+/// the call.
+pub type ProgressCallback = extern "C" fn(*const c_char);
+
+/// Forwards the events of `Prover` to the host of the library.
+pub(crate) struct CallbackObserver {
+    callback: ProgressCallback,
+}
+
+impl CallbackObserver {
+    pub(crate) fn new(callback: ProgressCallback) -> Self {
+        Self { callback }
+    }
+
+    fn emit(&self, event: serde_json::Value) {
+        let event = CString::new(event.to_string()).unwrap();
+        (self.callback)(event.as_ptr());
+    }
+}
+
+impl ProverObserver for CallbackObserver {
+    fn on_phase_start(&self, phase: ProverPhase, circuit: &str) {
+        self.emit(json!({
+            "event": "phase_start",
+            "phase": phase.as_str(),
+            "circuit": circuit,
+        }));
+    }
+
+    fn on_phase_end(&self, phase: ProverPhase, circuit: &str, elapsed: Duration) {
+        self.emit(json!({
+            "event": "phase_end",
+            "phase": phase.as_str(),
+            "circuit": circuit,
+            "elapsed_ms": elapsed.as_millis() as u64,
+        }));
+    }
+
+    fn on_row_usage(&self, block_number: Option<u64>, rows: &[(&'static str, usize)]) {
+        self.emit(json!({
+            "event": "row_usage",
+            "block": block_number,
+            "rows": rows.iter().cloned().collect::<std::collections::BTreeMap<_, _>>(),
+        }));
+    }
+
+    fn on_truncate(&self, kept: usize, total: usize) {
+        self.emit(json!({
+            "event": "truncate",
+            "kept": kept,
+            "total": total,
+        }));
+    }
+
+    fn on_memory_sample(&self, desc: &str, bytes: u64) {
+        self.emit(json!({
+            "event": "memory_sample",
+            "desc": desc,
+            "bytes": bytes,
+        }));
+    }
+
+    fn on_peak_rss(&self, phase: ProverPhase, circuit: &str, bytes: u64) {
+        self.emit(json!({
+            "event": "peak_rss",
+            "phase": phase.as_str(),
+            "circuit": circuit,
+            "bytes": bytes,
+        }));
+    }
+}
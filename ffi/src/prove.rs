@@ -1,11 +1,17 @@
+use crate::observer::{CallbackObserver, ProgressCallback};
 use crate::utils::{c_char_to_str, c_char_to_vec, vec_to_c_char};
 use libc::c_char;
 use std::cell::OnceCell;
+use std::sync::Arc;
 use types::eth::BlockTrace;
 use zkevm::prover::Prover;
 
 static mut PROVER: OnceCell<Prover> = OnceCell::new();
+static mut PROGRESS_CALLBACK: Option<ProgressCallback> = None;
 
+/// Initializes the prover with OS randomness. A non-empty `seed_path` switches to
+/// deterministic seeding, which is for tests only.
+///
 /// # Safety
 #[no_mangle]
 pub unsafe extern "C" fn init_prover(params_path: *const c_char, seed_path: *const c_char) {
@@ -13,10 +19,30 @@ pub unsafe extern "C" fn init_prover(params_path: *const c_char, seed_path: *con
 
     let params_path = c_char_to_str(params_path);
     let seed_path = c_char_to_str(seed_path);
-    let p = Prover::from_fpath(params_path, seed_path);
+    let mut p = if seed_path.is_empty() {
+        Prover::from_fpath(params_path)
+    } else {
+        Prover::from_fpath_and_seed(params_path, seed_path)
+    };
+    if let Some(callback) = PROGRESS_CALLBACK {
+        p.observer = Arc::new(CallbackObserver::new(callback));
+    }
     PROVER.set(p).unwrap();
 }
 
+/// Reports the progress of the prover to `callback`, as json objects with an `event` field.
+/// Without a callback, the progress is logged. Set before `init_prover`, the callback is
+/// applied once the prover is initialized.
+///
+/// # Safety
+#[no_mangle]
+pub unsafe extern "C" fn set_progress_callback(callback: ProgressCallback) {
+    PROGRESS_CALLBACK = Some(callback);
+    if let Some(prover) = PROVER.get_mut() {
+        prover.observer = Arc::new(CallbackObserver::new(callback));
+    }
+}
+
 /// # Safety
 #[no_mangle]
 pub unsafe extern "C" fn create_agg_proof(trace_char: *const c_char) -> *const c_char {
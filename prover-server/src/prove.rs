@@ -1,17 +1,17 @@
 use crate::prover_error::ProverError;
-use crate::utils::{kroma_info, kroma_msg};
+use crate::utils::{kroma_info, kroma_msg, KromaObserver};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use types::eth::BlockTrace;
 use utils::Measurer;
 use zkevm::circuit::{AGG_DEGREE, DEGREE};
 use zkevm::prover::{AggCircuitProof, Prover};
-use zkevm::utils::{load_kzg_params, load_or_create_seed};
+use zkevm::utils::load_kzg_params;
 
 pub const PARAMS_DIR: &str = "./kzg_params/";
-const SEED_FILE: &str = "./rng_seed";
 const OUT_PROOF_DIR: &str = "./out_proof/";
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -28,16 +28,14 @@ impl ProofResult {
 
 pub fn create_proof(trace: BlockTrace) -> Result<ProofResult, ProverError> {
     // load or create material for prover
-    let params = load_kzg_params(PARAMS_DIR, *DEGREE);
-    let agg_params = load_kzg_params(PARAMS_DIR, *AGG_DEGREE);
-    if params.is_err() || agg_params.is_err() {
-        return Err(ProverError::kzg_params_not_found());
-    }
-    let params = params.unwrap();
-    let agg_params = agg_params.unwrap();
-
-    let seed = load_or_create_seed(SEED_FILE)
-        .unwrap_or_else(|_| panic!("{}", kroma_msg("failed to load or create seed")));
+    let load = |degree| {
+        load_kzg_params(PARAMS_DIR, degree).map_err(|e| match e {
+            zkevm::Error::Io(_) => ProverError::kzg_params_not_found(),
+            e => e.into(),
+        })
+    };
+    let params = load(*DEGREE)?;
+    let agg_params = load(*AGG_DEGREE)?;
 
     // prepare directory to store proof. (i.e., ./out_proof/<block_number>/)
     let height_hex = trace.header.number.unwrap().to_string();
@@ -45,9 +43,11 @@ pub fn create_proof(trace: BlockTrace) -> Result<ProofResult, ProverError> {
     let _ = create_dir_all(&out_dir);
 
     // build prover
-    let mut prover = Prover::from_params_and_seed(params, Some(agg_params), seed);
+    let mut prover = Prover::new(params, Some(agg_params));
+    prover.agg_params_dir = Some(PARAMS_DIR.to_string());
     // specify the dir to store the vk and proof of the intermediate circuit.
     prover.debug_dir = out_dir.to_str().unwrap().to_string();
+    prover.observer = Arc::new(KromaObserver);
 
     create_agg_proof(prover, trace)
 }
@@ -57,9 +57,7 @@ pub fn create_agg_proof(mut prover: Prover, trace: BlockTrace) -> Result<ProofRe
 
     // generate proof
     let mut timer = Measurer::new();
-    let proof = prover
-        .create_agg_circuit_proof(&trace, false)
-        .unwrap_or_else(|_| panic!("{}", kroma_msg("cannot generate agg_proof")));
+    let proof = prover.create_agg_circuit_proof(&trace, false)?;
     timer.end(&kroma_msg("finish generating a proof"));
 
     // store proof and verifier contract as files
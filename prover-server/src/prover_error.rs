@@ -2,7 +2,7 @@ use jsonrpc_http_server::jsonrpc_core::{Error as JsonError, ErrorCode as JsonErr
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
 use std::env;
-use zkevm::circuit::MAX_TXS;
+use zkevm::circuit::CIRCUIT_PROFILE;
 
 use crate::utils::kroma_err;
 
@@ -17,10 +17,38 @@ pub enum ErrorCode {
     ChainIdNotMatched,
     /// Received a trace containing an unsupported OPCode.
     TraceVersionNotSupported,
-    /// Received a trace containing transactions that exceed `MAX_TXS`.
+    /// Received a trace lacking a block hash that `BLOCKHASH` reads.
+    BlockHashMissing,
+    /// Received a batch whose blocks do not build on each other.
+    BlockNotChained,
+    /// Received a trace whose deposit tx nonce disagrees with the state.
+    DepositNonceInvalid,
+    /// Received a trace whose tx gas price does not agree with its fee caps and base fee.
+    GasPriceInvalid,
+    /// Received a trace containing transactions that exceed `max_txs` of the circuit profile.
     TooManyTxs,
     /// Received a trace containing an unsupported OPCode.
     OPCodeNotSupported,
+    /// KZG parameters do not match the circuit configuration.
+    ParamsMismatch,
+    /// Failed to convert a trace into a witness block.
+    WitnessConversionError,
+    /// A sub-circuit needs more rows than the circuit has.
+    CapacityExceeded,
+    /// Received more blocks than the circuit can prove at once.
+    TooManyBlocks,
+    /// The circuit degree is lower than the one required by the trace.
+    DegreeInsufficient,
+    /// Failed to generate proving or verifying keys.
+    KeygenFailed,
+    /// Failed to create a proof.
+    ProofFailed,
+    /// Failed to verify a proof.
+    VerificationFailed,
+    /// Any other error raised while proving.
+    InternalError,
+    /// The prover cannot fit the proving keys into its memory budget.
+    MemoryBudgetExceeded,
 }
 
 impl ErrorCode {
@@ -28,13 +56,28 @@ impl ErrorCode {
         match *self {
             // Human error starts with `1`
             ErrorCode::KZGParamsNotFound => 1000,
+            ErrorCode::ParamsMismatch => 1001,
             // Trace error starts with `2`
             ErrorCode::TraceParseError => 2000,
             ErrorCode::ChainIdNotMatched => 2001,
             ErrorCode::TraceVersionNotSupported => 2002,
+            ErrorCode::WitnessConversionError => 2003,
+            ErrorCode::BlockHashMissing => 2004,
+            ErrorCode::BlockNotChained => 2005,
+            ErrorCode::DepositNonceInvalid => 2006,
+            ErrorCode::GasPriceInvalid => 2007,
             // Spec. error starts with `3`
             ErrorCode::TooManyTxs => 3000,
             ErrorCode::OPCodeNotSupported => 3001,
+            ErrorCode::CapacityExceeded => 3002,
+            ErrorCode::TooManyBlocks => 3003,
+            ErrorCode::DegreeInsufficient => 3004,
+            // Proving error starts with `4`
+            ErrorCode::KeygenFailed => 4000,
+            ErrorCode::ProofFailed => 4001,
+            ErrorCode::VerificationFailed => 4002,
+            ErrorCode::InternalError => 4003,
+            ErrorCode::MemoryBudgetExceeded => 4004,
         }
     }
 }
@@ -43,11 +86,25 @@ impl From<i64> for ErrorCode {
     fn from(code: i64) -> Self {
         match code {
             1000 => ErrorCode::KZGParamsNotFound,
+            1001 => ErrorCode::ParamsMismatch,
             2000 => ErrorCode::TraceParseError,
             2001 => ErrorCode::ChainIdNotMatched,
             2002 => ErrorCode::TraceVersionNotSupported,
+            2003 => ErrorCode::WitnessConversionError,
+            2004 => ErrorCode::BlockHashMissing,
+            2005 => ErrorCode::BlockNotChained,
+            2006 => ErrorCode::DepositNonceInvalid,
+            2007 => ErrorCode::GasPriceInvalid,
             3000 => ErrorCode::TooManyTxs,
             3001 => ErrorCode::OPCodeNotSupported,
+            3002 => ErrorCode::CapacityExceeded,
+            3003 => ErrorCode::TooManyBlocks,
+            3004 => ErrorCode::DegreeInsufficient,
+            4000 => ErrorCode::KeygenFailed,
+            4001 => ErrorCode::ProofFailed,
+            4002 => ErrorCode::VerificationFailed,
+            4003 => ErrorCode::InternalError,
+            4004 => ErrorCode::MemoryBudgetExceeded,
             _ => panic!("not supported code: {:?}", code),
         }
     }
@@ -129,7 +186,7 @@ impl ProverError {
     pub fn too_many_txs(trace_tx_num: usize) -> Self {
         let msg = format!(
             "Too may txs, max_txs({:?}), actual({:?})",
-            MAX_TXS, trace_tx_num
+            CIRCUIT_PROFILE.max_txs, trace_tx_num
         );
         let err = Self::new(ErrorCode::TooManyTxs, Some(msg));
         kroma_err(err.to_string());
@@ -144,6 +201,40 @@ impl ProverError {
     }
 }
 
+impl From<zkevm::Error> for ProverError {
+    fn from(err: zkevm::Error) -> Self {
+        let code = match &err {
+            zkevm::Error::ParamsMismatch(_) => ErrorCode::ParamsMismatch,
+            zkevm::Error::WitnessConversion(_) | zkevm::Error::TraceBundle(_) => {
+                ErrorCode::WitnessConversionError
+            }
+            zkevm::Error::ChainIdMismatch { .. } => ErrorCode::ChainIdNotMatched,
+            zkevm::Error::MissingBlockHash { .. } => ErrorCode::BlockHashMissing,
+            zkevm::Error::BlockNotChained { .. } => ErrorCode::BlockNotChained,
+            zkevm::Error::DepositNonceMismatch { .. } => ErrorCode::DepositNonceInvalid,
+            zkevm::Error::InvalidGasPrice { .. } => ErrorCode::GasPriceInvalid,
+            zkevm::Error::CapacityExceeded { .. } => ErrorCode::CapacityExceeded,
+            zkevm::Error::TooManyBlocks { .. } => ErrorCode::TooManyBlocks,
+            zkevm::Error::DegreeInsufficient { .. } => ErrorCode::DegreeInsufficient,
+            zkevm::Error::Keygen { .. } => ErrorCode::KeygenFailed,
+            zkevm::Error::Proof { .. } | zkevm::Error::MockProve { .. } => ErrorCode::ProofFailed,
+            zkevm::Error::Verification { .. }
+            | zkevm::Error::InvalidProof(_)
+            | zkevm::Error::VkNotFound(_)
+            | zkevm::Error::VkDigestMismatch { .. } => ErrorCode::VerificationFailed,
+            zkevm::Error::MemoryBudgetExceeded { .. } | zkevm::Error::AggParamsReleased => {
+                ErrorCode::MemoryBudgetExceeded
+            }
+            zkevm::Error::Unsupported(_) | zkevm::Error::Io(_) | zkevm::Error::Serde(_) => {
+                ErrorCode::InternalError
+            }
+        };
+        let err = Self::new(code, Some(err.to_string()));
+        kroma_err(err.to_string());
+        err
+    }
+}
+
 impl std::fmt::Display for ProverError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -7,11 +7,12 @@ use prover_server::prove;
 use prover_server::prover_error::ProverError;
 use prover_server::spec::ZkSpec;
 use prover_server::utils::{
-    is_cancun_trace, kroma_info, panic_if_kzg_params_is_not_official, panic_if_kzg_params_not_found,
+    is_cancun_trace, kroma_info, panic_if_circuit_profile_unknown,
+    panic_if_kzg_params_is_not_official, panic_if_kzg_params_not_found,
 };
 use types::eth::BlockTrace;
 use utils::{check_chain_id, is_tachyon};
-use zkevm::circuit::{CHAIN_ID, MAX_TXS};
+use zkevm::circuit::{check_chain_id, validate_block_trace, CHAIN_ID, CIRCUIT_PROFILE};
 use zkevm::version::{self, check_trace_version, panic_if_wrong_circuit_version};
 
 #[rpc]
@@ -27,6 +28,9 @@ pub trait Rpc {
     /// 3. pub chain_id: u32,
     /// 4. pub max_txs: u32,
     /// 5. pub max_call_data: u32,
+    /// 6. pub prover_version: String,
+    /// 7. pub vk_digests: HashMap<String, String>,
+    /// 8. pub circuit_profile: String,
     fn spec(&self) -> JsonResult<ZkSpec> {
         let spec = ZkSpec::new(*CHAIN_ID as u32);
         Ok(spec)
@@ -69,16 +73,18 @@ impl Rpc for RpcImpl {
 
         // check number of txs in the trace
         let tx_count = block_trace.transactions.len();
-        if tx_count > MAX_TXS {
+        if tx_count > CIRCUIT_PROFILE.max_txs {
             let err = ProverError::too_many_txs(tx_count);
             return JsonResult::Err(JsonError::from(err));
         }
 
-        // check chain id
-        let trace_chain_id = block_trace.chain_id.as_u64();
-        if *CHAIN_ID != trace_chain_id {
-            let err = ProverError::chain_id_not_matched(trace_chain_id);
-            return JsonResult::Err(JsonError::from(err));
+        // check chain id of the block and its txs
+        if let Err(e) = check_chain_id(&block_trace, *CHAIN_ID) {
+            return JsonResult::Err(JsonError::from(ProverError::from(e)));
+        }
+
+        if let Err(e) = validate_block_trace(&block_trace) {
+            return JsonResult::Err(JsonError::from(ProverError::from(e)));
         }
 
         match create_proof(block_trace) {
@@ -109,6 +115,7 @@ fn main() {
     dotenv::dotenv().ok();
     env_logger::init();
 
+    panic_if_circuit_profile_unknown();
     panic_if_wrong_circuit_version();
     panic_if_kzg_params_not_found(PARAMS_DIR);
     panic_if_kzg_params_is_not_official(PARAMS_DIR);
@@ -125,9 +132,10 @@ fn main() {
 
     kroma_info(format!("Prover server starting on {endpoint}."));
     kroma_info(format!(
-        "ChainId: {:?}, ProverVersion: {:?}, Tachyon: {:?}",
+        "ChainId: {:?}, ProverVersion: {:?}, CircuitProfile: {}, Tachyon: {:?}",
         chain_id,
         version::as_string(),
+        *CIRCUIT_PROFILE,
         is_tachyon()
     ));
     let server = ServerBuilder::new(io)
@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+
 use serde_derive::{Deserialize, Serialize};
-use zkevm::circuit::{AGG_DEGREE, CHAIN_ID, DEGREE, MAX_CALLDATA, MAX_TXS};
+use zkevm::circuit::{AGG_DEGREE, CHAIN_ID, CIRCUIT_PROFILE, DEGREE};
+use zkevm::version;
+use zkevm::vk_registry::{VkRegistry, VK_REGISTRY_PATH};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ZkSpec {
@@ -8,17 +12,19 @@ pub struct ZkSpec {
     pub chain_id: u32,
     pub max_txs: u32,
     pub max_call_data: u32,
+    #[serde(default)]
+    pub circuit_profile: String,
+    #[serde(default)]
+    pub prover_version: String,
+    /// Registered vk digests of this prover version and circuit config, keyed by circuit
+    /// name. Empty if the vk registry has no such entry.
+    #[serde(default)]
+    pub vk_digests: HashMap<String, String>,
 }
 
 impl Default for ZkSpec {
     fn default() -> Self {
-        Self {
-            degree: *DEGREE as u32,
-            agg_degree: *AGG_DEGREE as u32,
-            chain_id: *CHAIN_ID as u32,
-            max_txs: MAX_TXS as u32,
-            max_call_data: MAX_CALLDATA as u32,
-        }
+        Self::new(*CHAIN_ID as u32)
     }
 }
 
@@ -28,8 +34,28 @@ impl ZkSpec {
             degree: *DEGREE as u32,
             agg_degree: *AGG_DEGREE as u32,
             chain_id,
-            max_txs: MAX_TXS as u32,
-            max_call_data: MAX_CALLDATA as u32,
+            max_txs: CIRCUIT_PROFILE.max_txs as u32,
+            max_call_data: CIRCUIT_PROFILE.max_calldata as u32,
+            circuit_profile: CIRCUIT_PROFILE.to_string(),
+            prover_version: version::as_string(),
+            vk_digests: registered_vk_digests(),
+        }
+    }
+}
+
+fn registered_vk_digests() -> HashMap<String, String> {
+    let registry = match VkRegistry::load(VK_REGISTRY_PATH.as_str()) {
+        Ok(registry) => registry,
+        Err(e) => {
+            log::warn!("failed to load vk registry {}: {}", *VK_REGISTRY_PATH, e);
+            return HashMap::new();
+        }
+    };
+    match registry.current() {
+        Ok(entry) => entry.digests.clone(),
+        Err(e) => {
+            log::warn!("{}", e);
+            HashMap::new()
         }
     }
 }
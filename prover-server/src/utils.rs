@@ -8,8 +8,10 @@ use std::{
     fs::File,
     io::{BufReader, Read},
     path::Path,
+    time::Duration,
 };
-use zkevm::circuit::{AGG_DEGREE, DEGREE};
+use zkevm::circuit::{circuit_profile_from_env, AGG_DEGREE, DEGREE};
+use zkevm::prover::{ProverObserver, ProverPhase};
 
 pub static KROMA_MSG_HEADER: &str = "KROMA";
 
@@ -25,6 +27,49 @@ pub fn kroma_err<S: AsRef<str> + Display>(msg: S) {
     error!("{}", kroma_msg(msg))
 }
 
+/// Reports the progress of `Prover` with the `KROMA` message header.
+pub struct KromaObserver;
+
+impl ProverObserver for KromaObserver {
+    fn on_phase_start(&self, phase: ProverPhase, circuit: &str) {
+        kroma_info(format!("start {phase} of {circuit} circuit"));
+    }
+
+    fn on_phase_end(&self, phase: ProverPhase, circuit: &str, elapsed: Duration) {
+        kroma_info(format!(
+            "finish {phase} of {circuit} circuit, elapsed: {elapsed:?}"
+        ));
+    }
+
+    fn on_row_usage(&self, block_number: Option<u64>, rows: &[(&'static str, usize)]) {
+        log::debug!(
+            "{}",
+            kroma_msg(format!("row usage after block {block_number:?}: {rows:?}"))
+        );
+    }
+
+    fn on_truncate(&self, kept: usize, total: usize) {
+        kroma_err(format!("batch truncated, proving {kept} of {total} blocks"));
+    }
+
+    fn on_memory_sample(&self, desc: &str, bytes: u64) {
+        log::debug!(
+            "{}",
+            kroma_msg(format!(
+                "memory usage when {desc}: {:?}GB",
+                bytes / 1024 / 1024 / 1024
+            ))
+        );
+    }
+
+    fn on_peak_rss(&self, phase: ProverPhase, circuit: &str, bytes: u64) {
+        kroma_info(format!(
+            "peak memory of {phase} of {circuit} circuit: {:.2}GB",
+            bytes as f64 / (1u64 << 30) as f64
+        ));
+    }
+}
+
 pub fn is_cancun_trace(trace_json: &String) -> bool {
     trace_json.contains("TSTORE") || trace_json.contains("TLOAD") || trace_json.contains("MCOPY")
 }
@@ -34,6 +79,14 @@ fn check_kzg_param_exists(params_dir: &str, degree: usize) -> bool {
     Path::new(&params_path).exists()
 }
 
+/// Checks `CIRCUIT_PROFILE` before anything reads it, since it decides the circuits and the
+/// degree the server proves with.
+pub fn panic_if_circuit_profile_unknown() {
+    if let Err(e) = circuit_profile_from_env() {
+        panic!("invalid CIRCUIT_PROFILE: {e}")
+    }
+}
+
 pub fn panic_if_kzg_params_not_found(params_dir: &str) {
     if !check_kzg_param_exists(params_dir, *AGG_DEGREE) {
         panic!("kzg params for degree {} not found", *AGG_DEGREE)
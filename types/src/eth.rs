@@ -2,7 +2,7 @@ use eth_types::evm_types::{Gas, GasCost, OpcodeId, ProgramCounter, Stack, Storag
 use eth_types::geth_types::DEPOSIT_TX_TYPE;
 use eth_types::{AccessList, Block, GethExecStep, GethExecTrace, Hash, Transaction, Word, H256};
 use ethers_core::types::{Address, Bytes, U256, U64};
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 
 fn u64_to_word<'de, D>(deserializer: D) -> Result<U256, D::Error>
@@ -13,10 +13,22 @@ where
     Ok((i).into())
 }
 
+fn word_to_u64<S>(word: &U256, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u64(word.as_u64())
+}
+
 #[derive(Deserialize, Serialize, Default, Debug, Clone)]
 pub struct BlockTrace {
     pub version: String,
-    #[serde(rename = "chainID", default, deserialize_with = "u64_to_word")]
+    #[serde(
+        rename = "chainID",
+        default,
+        deserialize_with = "u64_to_word",
+        serialize_with = "word_to_u64"
+    )]
     pub chain_id: U256,
     pub coinbase: AccountProofWrapper,
     pub header: EthBlock,
@@ -25,6 +37,9 @@ pub struct BlockTrace {
     pub execution_results: Vec<ExecutionResult>,
     #[serde(rename = "storageTrace")]
     pub storage_trace: StorageTrace,
+    /// Hashes of up to 256 blocks before this one, oldest first, as `BLOCKHASH` reads them.
+    #[serde(rename = "blockHashes", default)]
+    pub block_hashes: Option<Vec<H256>>,
     //    #[serde(rename = "mptwitness", default)]
     //    pub mpt_witness: Vec<SMTTrace>,
 }
@@ -50,7 +65,7 @@ impl From<BlockTrace> for EthBlock {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
 pub struct TransactionTrace {
     // FIXME after traces upgraded
     #[serde(default, rename = "txHash")]
@@ -58,9 +73,18 @@ pub struct TransactionTrace {
     #[serde(rename = "type")]
     pub type_: u8,
     pub nonce: u64,
+    /// Nonce of a deposit tx, whose `nonce` is left at 0. Traces report it as op-geth reports
+    /// `depositNonce` in the receipts of deposit txs since Regolith; older traces omit it.
+    #[serde(rename = "depositNonce", default)]
+    pub deposit_nonce: Option<u64>,
     pub gas: u64,
     #[serde(rename = "gasPrice")]
     pub gas_price: U256,
+    /// Fee caps of an EIP-1559 tx, absent for the other tx types.
+    #[serde(rename = "maxFeePerGas", default)]
+    pub max_fee_per_gas: Option<U256>,
+    #[serde(rename = "maxPriorityFeePerGas", default)]
+    pub max_priority_fee_per_gas: Option<U256>,
     pub from: Address,
     pub to: Option<Address>,
     #[serde(rename = "chainId")]
@@ -105,8 +129,8 @@ impl TransactionTrace {
             s: self.s,
             transaction_type: Some(U64::from(self.type_)),
             access_list: self.access_list.clone(),
-            max_priority_fee_per_gas: None,
-            max_fee_per_gas: None,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas,
+            max_fee_per_gas: self.max_fee_per_gas,
             chain_id: Some(self.chain_id),
             other: Default::default(),
         };
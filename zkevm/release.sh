@@ -4,6 +4,9 @@ set -o pipefail
 
 export OPT_MEM=true
 #export MOCK_PROVE=true
+#export DUMP_TRACE_BUNDLE=true
+#export MEMORY_BUDGET_GB=256
+#export CIRCUIT_PROFILE=k19-devnet # or k21-batch
 #export KECCAK_ROWS=20
 #export KECCAK_DEGREE=19
 export RUST_MIN_STACK=100000000
@@ -1,5 +1,3 @@
-use anyhow::bail;
-
 use halo2_proofs::halo2curves::bn256::Fr;
 use halo2_proofs::plonk::Circuit as Halo2Circuit;
 
@@ -16,33 +14,39 @@ use zkevm_circuits::util::SubCircuit;
 use zkevm_circuits::witness;
 
 mod builder;
-
+mod estimator;
+mod planner;
+mod profile;
+mod registry;
+mod trace_bundle;
+mod validation;
+mod witness;
+
+use crate::error::{Error, Result};
 use crate::utils::read_env_var;
 
 pub use self::builder::{
-    block_traces_to_witness_block, calculate_row_usage_of_trace,
-    calculate_row_usage_of_witness_block, check_batch_capacity, SUB_CIRCUIT_NAMES,
+    block_traces_to_witness_block, block_traces_to_witness_block_with_chain_id,
+    calculate_row_usage_of_trace, calculate_row_usage_of_witness_block, check_batch_capacity,
+    SUB_CIRCUIT_NAMES,
 };
+pub use self::estimator::{estimate_row_usage_of_trace, ESTIMATE_ERROR_BOUND};
+pub use self::planner::{
+    max_rows_per_chunk, plan_batches, plan_batches_estimated, plan_batches_with, plan_chunks,
+    BatchPlan, ChunkLimit, ChunkPlan,
+};
+pub use self::profile::{circuit_profile_from_env, CircuitProfile, CIRCUIT_PROFILE};
+pub use self::registry::{target_circuit, target_circuit_names, DynTargetCircuit};
+pub use self::trace_bundle::{
+    TraceBundle, TraceBundleHeader, TRACE_BUNDLE_FORMAT, TRACE_BUNDLE_MAGIC,
+};
+pub use self::validation::{
+    check_chain_id, check_deposit_nonces, history_hashes, validate_batch, validate_block_trace,
+    ChainTip, BLOCK_HASH_HISTORY,
+};
+pub use self::witness::WitnessBuilder;
 
-////// params for degree = 19 ////////////
-/*
-pub static DEGREE: Lazy<usize> = Lazy::new(|| read_env_var("DEGREE", 19));
-const MAX_TXS: usize = 44;
-const MAX_INNER_BLOCKS: usize = 100;
-const MAX_CALLDATA: usize = 400_000;
-const MAX_RWS: usize = 500_000;
-const MAX_KECCAK_ROWS: usize = 524_000;
-const MAX_EXP_STEPS: usize = 10_000;
-*/
-
-////// params for degree = 20 ////////////
-pub static DEGREE: Lazy<usize> = Lazy::new(|| read_env_var("DEGREE", 21));
-pub const MAX_TXS: usize = 100;
-const MAX_INNER_BLOCKS: usize = 1;
-pub const MAX_CALLDATA: usize = 2_000_000;
-const MAX_RWS: usize = 2_000_000;
-const MAX_KECCAK_ROWS: usize = 1_000_000;
-const MAX_EXP_STEPS: usize = 100_000;
+pub static DEGREE: Lazy<usize> = Lazy::new(|| read_env_var("DEGREE", CIRCUIT_PROFILE.degree));
 
 pub static CHAIN_ID: Lazy<u64> = Lazy::new(|| read_env_var("CHAIN_ID", 255));
 pub static AGG_DEGREE: Lazy<usize> = Lazy::new(|| read_env_var("AGG_DEGREE", 26));
@@ -58,13 +62,13 @@ pub trait TargetCircuit {
     {
         Self::from_block_traces(&[]).unwrap().0
     }
-    fn from_block_trace(block_trace: &BlockTrace) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    fn from_block_trace(block_trace: &BlockTrace) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized,
     {
         Self::from_block_traces(std::slice::from_ref(block_trace))
     }
-    fn from_block_traces(block_traces: &[BlockTrace]) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    fn from_block_traces(block_traces: &[BlockTrace]) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized,
     {
@@ -73,11 +77,11 @@ pub trait TargetCircuit {
     }
     fn from_witness_block(
         witness_block: &witness::Block<Fr>,
-    ) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    ) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized;
 
-    fn estimate_rows(block_traces: &[BlockTrace]) -> anyhow::Result<usize> {
+    fn estimate_rows(block_traces: &[BlockTrace]) -> Result<usize> {
         let witness_block = block_traces_to_witness_block(block_traces)?;
         Ok(Self::estimate_rows_from_witness_block(&witness_block))
     }
@@ -89,28 +93,49 @@ pub trait TargetCircuit {
     }
 }
 
-pub struct SuperCircuit {}
+/// SuperCircuit with the limits of a [`CircuitProfile`].
+pub struct SuperCircuitOf<
+    const MAX_TXS: usize,
+    const MAX_CALLDATA: usize,
+    const MAX_INNER_BLOCKS: usize,
+> {}
+
+impl<const MAX_TXS: usize, const MAX_CALLDATA: usize, const MAX_INNER_BLOCKS: usize>
+    SuperCircuitOf<MAX_TXS, MAX_CALLDATA, MAX_INNER_BLOCKS>
+{
+    pub const MAX_TXS: usize = MAX_TXS;
+    pub const MAX_CALLDATA: usize = MAX_CALLDATA;
+    pub const MAX_INNER_BLOCKS: usize = MAX_INNER_BLOCKS;
+}
+
+/// SuperCircuit of [`CircuitProfile::K21_MAINNET`].
+pub type SuperCircuit = SuperCircuitOf<100, 2_000_000, 1>;
+/// SuperCircuit of [`CircuitProfile::K19_DEVNET`].
+pub type DevnetSuperCircuit = SuperCircuitOf<44, 400_000, 100>;
+/// SuperCircuit of [`CircuitProfile::K21_BATCH`].
+pub type BatchSuperCircuit = SuperCircuitOf<100, 2_000_000, 16>;
 
-impl TargetCircuit for SuperCircuit {
+impl<const MAX_TXS: usize, const MAX_CALLDATA: usize, const MAX_INNER_BLOCKS: usize> TargetCircuit
+    for SuperCircuitOf<MAX_TXS, MAX_CALLDATA, MAX_INNER_BLOCKS>
+{
     type Inner = SuperCircuitImpl<Fr, MAX_TXS, MAX_CALLDATA, MAX_INNER_BLOCKS, 0x1000>;
 
     fn name() -> String {
-        "super".to_string()
+        CircuitProfile::of_super_circuit(MAX_TXS, MAX_CALLDATA, MAX_INNER_BLOCKS)
+            .super_circuit_name()
     }
 
-    fn from_witness_block(
-        witness_block: &witness::Block<Fr>,
-    ) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    fn from_witness_block(witness_block: &witness::Block<Fr>) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized,
     {
-        let (k, inner, instance) = Self::Inner::build_from_witness_block(witness_block.clone())?;
+        let (k, inner, instance) =
+            Self::Inner::build_from_witness_block(witness_block.clone()).map_err(Error::witness)?;
         if k as usize > *DEGREE {
-            bail!(
-                "circuit not enough: DEGREE = {}, less than k needed: {}",
-                *DEGREE,
-                k
-            );
+            return Err(Error::DegreeInsufficient {
+                degree: *DEGREE,
+                k_needed: k,
+            });
         }
         Ok((inner, instance))
     }
@@ -133,9 +158,7 @@ impl TargetCircuit for EvmCircuit {
         "evm".to_string()
     }
 
-    fn from_witness_block(
-        witness_block: &witness::Block<Fr>,
-    ) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    fn from_witness_block(witness_block: &witness::Block<Fr>) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized,
     {
@@ -158,9 +181,7 @@ impl TargetCircuit for StateCircuit {
         StateCircuitImpl::<Fr>::default()
     }
 
-    fn from_witness_block(
-        witness_block: &witness::Block<Fr>,
-    ) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    fn from_witness_block(witness_block: &witness::Block<Fr>) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized,
     {
@@ -210,9 +231,7 @@ impl TargetCircuit for ZktrieCircuit {
         "zktrie".to_string()
     }
 
-    fn from_witness_block(
-        witness_block: &witness::Block<Fr>,
-    ) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    fn from_witness_block(witness_block: &witness::Block<Fr>) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized,
     {
@@ -231,9 +250,7 @@ impl TargetCircuit for PoseidonCircuit {
         "poseidon".to_string()
     }
 
-    fn from_witness_block(
-        witness_block: &witness::Block<Fr>,
-    ) -> anyhow::Result<(Self::Inner, Vec<Vec<Fr>>)>
+    fn from_witness_block(witness_block: &witness::Block<Fr>) -> Result<(Self::Inner, Vec<Vec<Fr>>)>
     where
         Self: Sized,
     {
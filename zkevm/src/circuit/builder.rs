@@ -1,22 +1,17 @@
-use super::{MAX_CALLDATA, MAX_EXP_STEPS, MAX_RWS, MAX_TXS};
 use crate::circuit::{
-    TargetCircuit, AUTO_TRUNCATE, CHAIN_ID, DEGREE, MAX_INNER_BLOCKS, MAX_KECCAK_ROWS,
+    max_rows_per_chunk, TargetCircuit, WitnessBuilder, AUTO_TRUNCATE, CHAIN_ID, CIRCUIT_PROFILE,
 };
-use anyhow::bail;
-use bus_mapping::circuit_input_builder::{self, BlockHead, CircuitInputBuilder, CircuitsParams};
-use bus_mapping::state_db::{Account, CodeDB, StateDB};
-use eth_types::{evm_types::OpcodeId, geth_types::DEPOSIT_TX_TYPE};
-use ethers_core::types::{Bytes, U256};
+use crate::error::{Error, Result};
+use crate::prover::ProverObserver;
+use bus_mapping::state_db::{CodeDB, StateDB};
+use eth_types::evm_types::OpcodeId;
+use ethers_core::types::Bytes;
 use halo2_proofs::halo2curves::bn256::Fr;
 use is_even::IsEven;
 use itertools::Itertools;
-use mpt_zktrie::state::ZktrieState;
 use std::time::Instant;
-use types::eth::{BlockTrace, EthBlock, ExecStep};
-use zkevm_circuits::{
-    evm_circuit::witness::{block_apply_mpt_state, block_convert, Block},
-    util::SubCircuit,
-};
+use types::eth::{BlockTrace, ExecStep};
+use zkevm_circuits::{evm_circuit::witness::Block, util::SubCircuit};
 
 #[cfg(not(feature = "zktrie"))]
 pub const SUB_CIRCUIT_NAMES: [&str; 10] = [
@@ -28,18 +23,15 @@ pub const SUB_CIRCUIT_NAMES: [&str; 11] = [
 ];
 
 // TODO: optimize it later
-pub fn calculate_row_usage_of_trace(block_trace: &BlockTrace) -> Result<Vec<usize>, anyhow::Error> {
+pub fn calculate_row_usage_of_trace(block_trace: &BlockTrace) -> Result<Vec<usize>> {
     let witness_block = block_traces_to_witness_block(std::slice::from_ref(block_trace))?;
     calculate_row_usage_of_witness_block(&witness_block)
 }
-pub fn calculate_row_usage_of_witness_block(
-    witness_block: &Block<Fr>,
-) -> Result<Vec<usize>, anyhow::Error> {
-    let rows =
-        <crate::circuit::SuperCircuit as TargetCircuit>::Inner::min_num_rows_block_subcircuits(
-            witness_block,
-        )
-        .0;
+pub fn calculate_row_usage_of_witness_block(witness_block: &Block<Fr>) -> Result<Vec<usize>> {
+    let rows = crate::with_super_circuit!(
+        *CIRCUIT_PROFILE,
+        C => <C as TargetCircuit>::Inner::min_num_rows_block_subcircuits(witness_block).0
+    );
 
     log::debug!(
         "row usage of block {:?}, tx num {:?}, tx len sum {}, rows needed {:?}",
@@ -55,8 +47,20 @@ pub fn calculate_row_usage_of_witness_block(
     Ok(rows)
 }
 
-/// ...
-pub fn check_batch_capacity(block_traces: &mut Vec<BlockTrace>) -> Result<(), anyhow::Error> {
+/// Truncates `block_traces` to the longest prefix that fits into the circuit, reporting
+/// the accumulated row usage and the truncation decision to `observer`. This keeps the first
+/// chunk of [`plan_batches`](crate::circuit::plan_batches) only. The row usage is always the
+/// exact one of each block, also for batches pre-split with
+/// [`plan_batches_estimated`](crate::circuit::plan_batches_estimated).
+///
+/// Blocks are added to the returned builder as they are checked, and the one that does not
+/// fit never is, so the builder holds the kept blocks and only needs to be finalized into
+/// their witness block. Every block and every non-deposit tx has to be of `chain_id`.
+pub fn check_batch_capacity(
+    block_traces: &mut Vec<BlockTrace>,
+    chain_id: u64,
+    observer: &dyn ProverObserver,
+) -> Result<WitnessBuilder> {
     let block_traces_len = block_traces.len();
     let total_tx_count = block_traces
         .iter()
@@ -73,44 +77,56 @@ pub fn check_batch_capacity(block_traces: &mut Vec<BlockTrace>) -> Result<(), an
         total_tx_len_sum
     );
 
-    if block_traces_len > MAX_INNER_BLOCKS {
-        bail!("too many blocks");
+    if block_traces_len > CIRCUIT_PROFILE.max_inner_blocks {
+        return Err(Error::TooManyBlocks {
+            blocks: block_traces_len,
+            max_blocks: CIRCUIT_PROFILE.max_inner_blocks,
+        });
     }
 
     if !*AUTO_TRUNCATE {
         log::debug!("AUTO_TRUNCATE=false, keep batch as is");
-        return Ok(());
+        let mut builder = WitnessBuilder::for_batch(block_traces, chain_id)?;
+        builder.add_blocks(block_traces)?;
+        return Ok(builder);
     }
 
     let t = Instant::now();
-    let mut acc = Vec::new();
+    let max_rows = max_rows_per_chunk();
+    let mut builder = WitnessBuilder::new(chain_id);
+    let mut acc = vec![0; SUB_CIRCUIT_NAMES.len()];
     let mut truncate_idx = block_traces.len();
+    // The usage of the next block decides whether the current one is the last of the batch.
+    let mut next_usage = block_traces
+        .first()
+        .map(|block| builder.block_row_usage(block))
+        .transpose()?;
     for (idx, block) in block_traces.iter().enumerate() {
-        let usage = calculate_row_usage_of_trace(block)?;
-        if acc.is_empty() {
-            acc = usage;
-        } else {
-            acc.iter_mut().zip(usage.iter()).for_each(|(acc, usage)| {
-                *acc += usage;
-            });
-        }
+        let usage = next_usage.take().unwrap();
+        acc = add_row_usage(&acc, &usage);
         let rows = itertools::max(&acc).unwrap();
         let rows_and_names: Vec<(_, _)> = SUB_CIRCUIT_NAMES
             .iter()
-            .zip_eq(acc.iter())
+            .copied()
+            .zip_eq(acc.iter().copied())
             .collect::<Vec<(_, _)>>();
-        log::debug!(
-            "row usage after block {}({:?}): {}, {:?}",
-            idx,
-            block.header.number,
-            rows,
-            rows_and_names
-        );
-        if *rows >= (1 << *DEGREE) - 256 {
-            log::warn!("truncate blocks [{}..{})", idx, block_traces_len);
+        observer.on_row_usage(block.header.number.map(|n| n.as_u64()), &rows_and_names);
+        if *rows >= max_rows {
             truncate_idx = idx;
             break;
         }
+        next_usage = block_traces
+            .get(idx + 1)
+            .map(|next| builder.block_row_usage(next))
+            .transpose()?;
+        let is_last = match next_usage.as_ref() {
+            Some(next) => itertools::max(add_row_usage(&acc, next)).unwrap() >= max_rows,
+            None => true,
+        };
+        builder.add_block_with_usage(block, is_last, &usage)?;
+    }
+    if truncate_idx < block_traces_len {
+        observer.on_truncate(truncate_idx, block_traces_len);
     }
     log::debug!("check_batch_capacity takes {:?}", t.elapsed());
     block_traces.truncate(truncate_idx);
@@ -120,157 +136,45 @@ pub fn check_batch_capacity(block_traces: &mut Vec<BlockTrace>) -> Result<(), an
         .sum::<usize>();
     if total_tx_count != 0 && total_tx_count2 == 0 {
         // the circuit cannot even prove the first non-empty block...
-        bail!("circuit capacity not enough");
-    }
-    Ok(())
-}
-
-pub fn block_traces_to_witness_block(
-    block_traces: &[BlockTrace],
-) -> Result<Block<Fr>, anyhow::Error> {
-    let old_root = if block_traces.is_empty() {
-        eth_types::Hash::zero()
-    } else {
-        block_traces[0].storage_trace.root_before
-    };
-    let zktrie_state = ZktrieState::from_trace_with_additional(
-        old_root,
-        block_traces.iter().rev().flat_map(|block| {
-            block.storage_trace.proofs.iter().flat_map(|kv_map| {
-                kv_map
-                    .iter()
-                    .map(|(k, bts)| (k, bts.iter().map(Bytes::as_ref)))
-            })
-        }),
-        block_traces.iter().rev().flat_map(|block| {
-            block
-                .storage_trace
-                .storage_proofs
-                .iter()
-                .flat_map(|(k, kv_map)| {
-                    kv_map
-                        .iter()
-                        .map(move |(sk, bts)| (k, sk, bts.iter().map(Bytes::as_ref)))
-                })
-        }),
-        block_traces.iter().rev().flat_map(|block| {
-            block
-                .storage_trace
-                .deletion_proofs
-                .iter()
-                .map(Bytes::as_ref)
-        }),
-    )?;
-
-    // TODO(dongchangYoo): It can be simplified after updating that deposit_tx has non-zero chain_id.
-    let chain_ids = block_traces
-        .iter()
-        .flat_map(|block_trace| {
-            block_trace
-                .transactions
-                .iter()
-                .find(|tx_trace| tx_trace.type_ as u64 != DEPOSIT_TX_TYPE)
-                .map(|tx_trace| tx_trace.chain_id)
-        })
-        .collect::<Vec<U256>>();
-
-    let chain_id = if !chain_ids.is_empty() {
-        chain_ids[0]
-    } else {
-        (*CHAIN_ID).into()
-    };
-
-    let mut state_db = zktrie_state.state().clone();
-
-    let (zero_coinbase_exist, _) = state_db.get_account(&Default::default());
-    if !zero_coinbase_exist {
-        state_db.set_account(&Default::default(), Account::zero());
-    }
-
-    let code_db = build_codedb(&state_db, block_traces)?;
-    let circuit_params = CircuitsParams {
-        max_evm_rows: MAX_RWS,
-        max_rws: MAX_RWS,
-        max_copy_rows: MAX_RWS,
-        max_txs: MAX_TXS,
-        max_calldata: MAX_CALLDATA,
-        max_bytecode: MAX_CALLDATA,
-        max_inner_blocks: MAX_INNER_BLOCKS,
-        max_keccak_rows: MAX_KECCAK_ROWS,
-        max_exp_steps: MAX_EXP_STEPS,
-    };
-    let mut builder_block = circuit_input_builder::Block::from_headers(&[], circuit_params);
-    builder_block.chain_id = chain_id;
-    builder_block.prev_state_root = U256::from(zktrie_state.root());
-    let mut builder = CircuitInputBuilder::new(state_db.clone(), code_db, &builder_block);
-    for (idx, block_trace) in block_traces.iter().enumerate() {
-        let is_last = idx == block_traces.len() - 1;
-        let mut eth_block: EthBlock = block_trace.clone().into();
-        eth_block.transactions.iter_mut().for_each(|transaction| {
-            if let Some(transaction_type) = transaction.transaction_type {
-                // NOTE(chokobole): The nonce of Kroma deposit tx is set to 0 by default.
-                // This causes an error at assert statement in zkevm-circuits.
-                // See gen_begin_tx_ops in bus-mappings/src/evm/opcodes.rs in zkevm-circuits for details.
-                // So here we explicitly set the known nonce from state db to the transaction.
-                // We have an alternative to make go-ethereum or Kroma-node to set nonce explicitly.
-                // But I think this is the fastest way to satisfy requirements.
-                if transaction_type.as_u64() == DEPOSIT_TX_TYPE {
-                    transaction.nonce = U256::from(builder.sdb.get_nonce(&transaction.from));
-                }
-            }
+        let (circuit, rows) = SUB_CIRCUIT_NAMES
+            .iter()
+            .zip_eq(acc.iter())
+            .max_by_key(|(_, rows)| **rows)
+            .map(|(name, rows)| (name.to_string(), *rows))
+            .unwrap_or_default();
+        return Err(Error::CapacityExceeded {
+            circuit,
+            rows,
+            max_rows,
         });
-
-        let mut geth_trace = Vec::new();
-        for result in &block_trace.execution_results {
-            geth_trace.push(result.into());
-        }
-        // TODO: Get the history_hashes.
-        let mut header = BlockHead::new(chain_id, Vec::new(), &eth_block)?;
-        // override zeroed minder field with additional "coinbase" field in blocktrace
-        if let Some(address) = block_trace.coinbase.address {
-            header.coinbase = address;
-        }
-
-        builder.block.headers.insert(header.number.as_u64(), header);
-        builder.handle_block_inner(&eth_block, geth_trace.as_slice(), false, is_last)?;
-
-        let per_block_metric = false;
-        if per_block_metric {
-            let t = Instant::now();
-            let block = block_convert::<Fr>(&builder.block, &builder.code_db)?;
-            log::debug!("block convert time {:?}", t.elapsed());
-            let rows =
-                <crate::circuit::SuperCircuit as TargetCircuit>::Inner::min_num_rows_block(&block);
-            log::debug!(
-                "after block {}, tx num {:?}, tx len sum {}, rows needed {:?}. estimate time: {:?}",
-                idx,
-                builder.block.txs().len(),
-                builder
-                    .block
-                    .txs()
-                    .iter()
-                    .map(|t| t.input.len())
-                    .sum::<usize>(),
-                rows,
-                t.elapsed()
-            );
-        }
     }
+    Ok(builder)
+}
 
-    builder.set_value_ops_call_context_rwc_eor();
-    builder.set_end_block()?;
+fn add_row_usage(acc: &[usize], usage: &[usize]) -> Vec<usize> {
+    acc.iter()
+        .zip_eq(usage)
+        .map(|(a, u)| a.saturating_add(*u))
+        .collect()
+}
 
-    let mut witness_block = block_convert(&builder.block, &builder.code_db)?;
-    log::debug!(
-        "witness_block.circuits_params {:?}",
-        witness_block.circuits_params
-    );
+/// Builds the witness of `block_traces` for the configured [`CHAIN_ID`].
+pub fn block_traces_to_witness_block(block_traces: &[BlockTrace]) -> Result<Block<Fr>> {
+    block_traces_to_witness_block_with_chain_id(block_traces, *CHAIN_ID)
+}
 
-    block_apply_mpt_state(&mut witness_block, zktrie_state);
-    Ok(witness_block)
+/// Builds the witness of `block_traces`, failing when a block or a non-deposit tx is not of
+/// `chain_id`.
+pub fn block_traces_to_witness_block_with_chain_id(
+    block_traces: &[BlockTrace],
+    chain_id: u64,
+) -> Result<Block<Fr>> {
+    let mut builder = WitnessBuilder::for_batch(block_traces, chain_id)?;
+    builder.add_blocks(block_traces)?;
+    builder.finalize()
 }
 
-pub fn decode_bytecode(bytecode: &str) -> Result<Vec<u8>, anyhow::Error> {
+pub fn decode_bytecode(bytecode: &str) -> Result<Vec<u8>> {
     let mut stripped = if let Some(stripped) = bytecode.strip_prefix("0x") {
         stripped.to_string()
     } else {
@@ -282,7 +186,7 @@ pub fn decode_bytecode(bytecode: &str) -> Result<Vec<u8>, anyhow::Error> {
         stripped = format!("0{stripped}");
     }
 
-    hex::decode(stripped).map_err(|e| e.into())
+    hex::decode(stripped).map_err(Error::witness)
 }
 /*
 #[derive(Debug, Clone)]
@@ -399,9 +303,14 @@ fn trace_code(cdb: &mut CodeDB, _step: &ExecStep, _sdb: &StateDB, code: Bytes, _
     cdb.insert(code.to_vec());
 }
 
-pub fn build_codedb(sdb: &StateDB, blocks: &[BlockTrace]) -> Result<CodeDB, anyhow::Error> {
+pub fn build_codedb(sdb: &StateDB, blocks: &[BlockTrace]) -> Result<CodeDB> {
     let mut cdb = CodeDB::new();
+    extend_codedb(&mut cdb, sdb, blocks)?;
+    Ok(cdb)
+}
 
+/// Inserts the codes `blocks` execute or read into `cdb`.
+pub(crate) fn extend_codedb(cdb: &mut CodeDB, sdb: &StateDB, blocks: &[BlockTrace]) -> Result<()> {
     for block in blocks.iter().rev() {
         // notice empty codehash always kept as keccak256(nil)
         cdb.insert(Vec::new());
@@ -431,7 +340,7 @@ pub fn build_codedb(sdb: &StateDB, blocks: &[BlockTrace]) -> Result<CodeDB, anyh
                             if callee_code.is_none() {
                                 log::error!("cannot get code of call: {:?}", step);
                             }
-                            trace_code(&mut cdb, step, sdb, callee_code.unwrap(), 1);
+                            trace_code(cdb, step, sdb, callee_code.unwrap(), 1);
                         }
                         OpcodeId::CREATE | OpcodeId::CREATE2 => {
                             // notice we do not need to insert code for CREATE,
@@ -442,7 +351,7 @@ pub fn build_codedb(sdb: &StateDB, blocks: &[BlockTrace]) -> Result<CodeDB, anyh
                             if code.is_none() {
                                 log::error!("cannot get code of ext: {:?}", step);
                             }
-                            trace_code(&mut cdb, step, sdb, code.unwrap(), 0);
+                            trace_code(cdb, step, sdb, code.unwrap(), 0);
                         }
 
                         _ => {}
@@ -452,7 +361,7 @@ pub fn build_codedb(sdb: &StateDB, blocks: &[BlockTrace]) -> Result<CodeDB, anyh
         }
     }
 
-    Ok(cdb)
+    Ok(())
 }
 
 /*
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+
+use eth_types::evm_types::OpcodeId;
+use eth_types::geth_types::DEPOSIT_TX_TYPE;
+use ethers_core::types::U256;
+use types::eth::{BlockTrace, ExecStep};
+
+use crate::circuit::builder::decode_bytecode;
+use crate::circuit::SUB_CIRCUIT_NAMES;
+use crate::error::Result;
+
+/// Share of the rows of a chunk left free for the error of [`estimate_row_usage_of_trace`]
+/// when blocks are pre-split with it. The error is not bounded per sub circuit, so a batch
+/// is only proven after `check_batch_capacity` confirmed it with the exact row usage.
+pub const ESTIMATE_ERROR_BOUND: f64 = 0.25;
+
+// Row costs of the sub circuits, taken from the layouts of the circuits and rounded up.
+/// Height of the tallest execution state of the evm circuit.
+const EVM_ROWS_PER_STEP: usize = 20;
+/// BeginTx and EndTx steps of every tx.
+const EVM_STEPS_PER_TX: usize = 2;
+/// Reads and writes of a step, without its memory accesses.
+const RWS_PER_STEP: usize = 4;
+/// Reads and writes of BeginTx and EndTx.
+const RWS_PER_TX: usize = 20;
+/// Rows of one keccak-f permutation in the packed keccak circuit.
+const KECCAK_ROWS_PER_PERMUTATION: usize = 300;
+const KECCAK_RATE: usize = 136;
+/// Tx table rows of the fixed fields of a tx.
+const TX_ROWS_PER_TX: usize = 14;
+/// Rlp bytes of a tx besides its calldata, for the signed and the unsigned encoding.
+const RLP_ROWS_PER_TX: usize = 2 * 160;
+/// Rows of one squaring step of the exponentiation circuit.
+const EXP_ROWS_PER_BIT: usize = 7;
+/// Pi rows of a block header and of a tx.
+const PI_ROWS_PER_BLOCK: usize = 16;
+const PI_ROWS_PER_TX: usize = 12;
+/// Rows of one poseidon permutation, and the field bytes it absorbs.
+const POSEIDON_ROWS_PER_HASH: usize = 9;
+const POSEIDON_BYTES_PER_HASH: usize = 31;
+/// Mpt circuit rows of a trie node on a storage proof.
+#[cfg(feature = "zktrie")]
+const MPT_ROWS_PER_NODE: usize = 2;
+
+/// Estimates the row usage of `block_trace` per sub circuit, in the order of
+/// [`SUB_CIRCUIT_NAMES`], from its exec steps, calldata and storage proofs only. Unlike
+/// `calculate_row_usage_of_trace` it builds neither the zktrie state nor the witness block,
+/// which makes it cheap enough to pre-split long block ranges, but not exact.
+pub fn estimate_row_usage_of_trace(block_trace: &BlockTrace) -> Result<Vec<usize>> {
+    let txs = block_trace.transactions.len();
+    let calldata = block_trace
+        .transactions
+        .iter()
+        .map(|tx| tx.data.len())
+        .sum::<usize>();
+    let steps = block_trace
+        .execution_results
+        .iter()
+        .flat_map(|result| result.exec_steps.iter())
+        .collect::<Vec<_>>();
+
+    let copied = steps
+        .iter()
+        .map(|step| copy_len(step))
+        .fold(0, usize::saturating_add);
+    let exp_bits = steps
+        .iter()
+        .filter(|step| step.op == OpcodeId::EXP)
+        .map(|step| stack_arg(step, 1).map_or(256, |exponent| exponent.bits()))
+        .sum::<usize>();
+    let mut keccak_inputs = steps
+        .iter()
+        .filter(|step| step.op == OpcodeId::SHA3)
+        .map(|step| stack_arg(step, 1).map_or(0, saturating_len))
+        .collect::<Vec<_>>();
+    keccak_inputs.extend(
+        block_trace
+            .transactions
+            .iter()
+            .filter(|tx| tx.type_ as u64 != DEPOSIT_TX_TYPE)
+            .map(|tx| tx.data.len() + RLP_ROWS_PER_TX / 2),
+    );
+
+    let codes = bytecodes(block_trace)?;
+    let code_len = codes.iter().map(|code| code.len()).sum::<usize>();
+    keccak_inputs.extend(codes.iter().map(|code| code.len()));
+    let keccak_permutations = keccak_inputs
+        .iter()
+        .map(|len| len / KECCAK_RATE + 1)
+        .fold(0, usize::saturating_add);
+
+    let trie_nodes = block_trace
+        .storage_trace
+        .proofs
+        .iter()
+        .flat_map(|proofs| proofs.values())
+        .chain(
+            block_trace
+                .storage_trace
+                .storage_proofs
+                .values()
+                .flat_map(|proofs| proofs.values()),
+        )
+        .map(|proof| proof.len())
+        .sum::<usize>();
+
+    let rows = SUB_CIRCUIT_NAMES
+        .iter()
+        .map(|name| match *name {
+            "evm" => (steps.len() + EVM_STEPS_PER_TX * txs + 1) * EVM_ROWS_PER_STEP,
+            "state" => (steps.len() * RWS_PER_STEP + txs * RWS_PER_TX)
+                .saturating_add(copied.saturating_mul(2)),
+            "bytecode" => code_len + codes.len(),
+            "copy" => copied.saturating_add(calldata).saturating_mul(2),
+            "keccak" => keccak_permutations.saturating_mul(KECCAK_ROWS_PER_PERMUTATION),
+            "tx" => txs * TX_ROWS_PER_TX + calldata,
+            "rlp" => txs * RLP_ROWS_PER_TX + 2 * calldata,
+            "exp" => exp_bits * EXP_ROWS_PER_BIT,
+            "pi" => PI_ROWS_PER_BLOCK + txs * PI_ROWS_PER_TX,
+            "poseidon" => {
+                (2 * trie_nodes + code_len / POSEIDON_BYTES_PER_HASH + codes.len())
+                    * POSEIDON_ROWS_PER_HASH
+            }
+            #[cfg(feature = "zktrie")]
+            "mpt" => trie_nodes * MPT_ROWS_PER_NODE,
+            _ => unreachable!("unknown sub circuit {name}"),
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// Item `n` of the stack of `step`, counted from the top.
+fn stack_arg(step: &ExecStep, n: usize) -> Option<U256> {
+    let stack = step.stack.as_ref()?;
+    stack.len().checked_sub(n + 1).map(|idx| stack[idx])
+}
+
+/// Bytes copied by `step`, as far as its stack tells.
+fn copy_len(step: &ExecStep) -> usize {
+    let n = match step.op {
+        OpcodeId::CALLDATACOPY | OpcodeId::CODECOPY | OpcodeId::RETURNDATACOPY => 2,
+        OpcodeId::EXTCODECOPY => 3,
+        OpcodeId::SHA3 | OpcodeId::RETURN | OpcodeId::REVERT => 1,
+        OpcodeId::LOG0 | OpcodeId::LOG1 | OpcodeId::LOG2 | OpcodeId::LOG3 | OpcodeId::LOG4 => 1,
+        _ => return 0,
+    };
+    stack_arg(step, n).map_or(0, saturating_len)
+}
+
+/// `len` as a usize, saturated rather than truncated, so that a huge length from the stack
+/// makes the estimate exceed any capacity instead of wrapping to a small one.
+fn saturating_len(len: U256) -> usize {
+    if len > U256::from(usize::MAX) {
+        usize::MAX
+    } else {
+        len.as_usize()
+    }
+}
+
+/// Distinct bytecodes the block executes or reads.
+fn bytecodes(block_trace: &BlockTrace) -> Result<Vec<Vec<u8>>> {
+    let mut codes = HashSet::new();
+    for result in block_trace.execution_results.iter() {
+        if let Some(bytecode) = &result.byte_code {
+            codes.insert(decode_bytecode(bytecode)?);
+        }
+        for step in result.exec_steps.iter() {
+            if let Some(code_list) = step.extra_data.as_ref().and_then(|d| d.code_list.as_ref()) {
+                codes.extend(code_list.iter().map(|code| code.to_vec()));
+            }
+        }
+    }
+    Ok(codes.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::estimate_row_usage_of_trace;
+    use crate::circuit::SUB_CIRCUIT_NAMES;
+    use eth_types::evm_types::OpcodeId;
+    use eth_types::Word;
+    use types::eth::{BlockTrace, ExecStep, ExecutionResult};
+
+    #[test]
+    fn test_estimate_saturates_huge_lengths() {
+        let step = ExecStep {
+            pc: 0,
+            op: OpcodeId::SHA3,
+            gas: 0,
+            gas_cost: 30,
+            refund: 0,
+            depth: 1,
+            error: None,
+            stack: Some(vec![Word::MAX, Word::zero()]),
+            memory: None,
+            storage: None,
+            extra_data: None,
+        };
+        let mut trace = BlockTrace::default();
+        trace.execution_results.push(ExecutionResult {
+            gas: 0,
+            failed: false,
+            return_value: String::new(),
+            from: None,
+            to: None,
+            account_after: vec![],
+            account_created: None,
+            code_hash: None,
+            byte_code: None,
+            exec_steps: vec![step],
+        });
+        let rows = estimate_row_usage_of_trace(&trace).unwrap();
+        for name in ["state", "copy", "keccak"] {
+            let idx = SUB_CIRCUIT_NAMES.iter().position(|n| *n == name).unwrap();
+            assert_eq!(rows[idx], usize::MAX, "{name}");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::ops::Range;
+
+use itertools::Itertools;
+use serde_derive::Serialize;
+use types::eth::BlockTrace;
+
+use crate::circuit::{
+    calculate_row_usage_of_trace, estimate_row_usage_of_trace, CIRCUIT_PROFILE, DEGREE,
+    ESTIMATE_ERROR_BOUND, SUB_CIRCUIT_NAMES,
+};
+use crate::error::{Error, Result};
+
+/// Rows a sub circuit may use in one chunk, leaving room for the blinding rows.
+pub fn max_rows_per_chunk() -> usize {
+    (1 << *DEGREE) - 256
+}
+
+/// Why a chunk ends where it does.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkLimit {
+    /// The chunk holds the last block of the range.
+    End,
+    /// Adding the next block would overflow `circuit`, which would need `rows` rows.
+    Rows { circuit: &'static str, rows: usize },
+    /// The chunk already holds `max_inner_blocks` blocks.
+    Blocks,
+}
+
+/// Consecutive blocks that are proven together.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChunkPlan {
+    /// Indices into the planned block range.
+    pub blocks: Range<usize>,
+    /// Row usage per sub circuit, in the order of [`SUB_CIRCUIT_NAMES`].
+    pub rows: Vec<(&'static str, usize)>,
+    pub limit: ChunkLimit,
+}
+
+impl ChunkPlan {
+    /// The sub circuit using the most rows, and its rows.
+    pub fn bottleneck(&self) -> (&'static str, usize) {
+        self.rows
+            .iter()
+            .copied()
+            .max_by_key(|(_, rows)| *rows)
+            .unwrap_or_default()
+    }
+
+    /// Rows used by the bottleneck over [`BatchPlan::max_rows`].
+    pub fn utilization(&self, max_rows: usize) -> f64 {
+        self.bottleneck().1 as f64 / max_rows as f64
+    }
+}
+
+/// Split of a block range into provable chunks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BatchPlan {
+    pub max_rows: usize,
+    pub max_blocks: usize,
+    pub chunks: Vec<ChunkPlan>,
+}
+
+/// Plans `block_traces` with the exact row usage of every block, see [`plan_chunks`].
+pub fn plan_batches(block_traces: &[BlockTrace]) -> Result<BatchPlan> {
+    plan_batches_with(block_traces, calculate_row_usage_of_trace)
+}
+
+/// Pre-splits `block_traces` with [`estimate_row_usage_of_trace`], leaving
+/// [`ESTIMATE_ERROR_BOUND`] of the rows of every chunk free. The chunks still have to pass
+/// `check_batch_capacity`, which may truncate them further.
+pub fn plan_batches_estimated(block_traces: &[BlockTrace]) -> Result<BatchPlan> {
+    let max_rows = (max_rows_per_chunk() as f64 * (1.0 - ESTIMATE_ERROR_BOUND)) as usize;
+    plan_batches_below(block_traces, estimate_row_usage_of_trace, max_rows)
+}
+
+/// Plans `block_traces` with the row usage given by `row_usage`.
+pub fn plan_batches_with(
+    block_traces: &[BlockTrace],
+    row_usage: impl Fn(&BlockTrace) -> Result<Vec<usize>>,
+) -> Result<BatchPlan> {
+    plan_batches_below(block_traces, row_usage, max_rows_per_chunk())
+}
+
+fn plan_batches_below(
+    block_traces: &[BlockTrace],
+    row_usage: impl Fn(&BlockTrace) -> Result<Vec<usize>>,
+    max_rows: usize,
+) -> Result<BatchPlan> {
+    let usages = block_traces
+        .iter()
+        .map(row_usage)
+        .collect::<Result<Vec<_>>>()?;
+    let max_blocks = CIRCUIT_PROFILE.max_inner_blocks;
+    Ok(BatchPlan {
+        max_rows,
+        max_blocks,
+        chunks: plan_chunks(&usages, max_rows, max_blocks)?,
+    })
+}
+
+/// Splits blocks with the per sub circuit row usages `usages` into consecutive chunks, each
+/// below `max_rows` in every sub circuit and with at most `max_blocks` blocks. Row usage is
+/// summed over the blocks of a chunk as in `check_batch_capacity`, so closing a chunk only
+/// when the next block does not fit gives the fewest chunks.
+pub fn plan_chunks(
+    usages: &[Vec<usize>],
+    max_rows: usize,
+    max_blocks: usize,
+) -> Result<Vec<ChunkPlan>> {
+    let named = |rows: &[usize]| {
+        SUB_CIRCUIT_NAMES
+            .iter()
+            .copied()
+            .zip_eq(rows.iter().copied())
+            .collect::<Vec<_>>()
+    };
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut acc = vec![0; SUB_CIRCUIT_NAMES.len()];
+    for (idx, usage) in usages.iter().enumerate() {
+        if let Some((circuit, rows)) = named(usage).into_iter().find(|(_, r)| *r >= max_rows) {
+            return Err(Error::CapacityExceeded {
+                circuit: format!("{circuit} of block {idx}"),
+                rows,
+                max_rows,
+            });
+        }
+
+        let next = acc
+            .iter()
+            .zip(usage)
+            .map(|(a, u)| a.saturating_add(*u))
+            .collect_vec();
+        let overflow = named(&next).into_iter().find(|(_, r)| *r >= max_rows);
+        let limit = match overflow {
+            Some((circuit, rows)) => Some(ChunkLimit::Rows { circuit, rows }),
+            None if idx - start == max_blocks => Some(ChunkLimit::Blocks),
+            None => None,
+        };
+        match limit {
+            Some(limit) => {
+                chunks.push(ChunkPlan {
+                    blocks: start..idx,
+                    rows: named(&acc),
+                    limit,
+                });
+                start = idx;
+                acc = usage.clone();
+            }
+            None => acc = next,
+        }
+    }
+    if start < usages.len() {
+        chunks.push(ChunkPlan {
+            blocks: start..usages.len(),
+            rows: named(&acc),
+            limit: ChunkLimit::End,
+        });
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plan_chunks, ChunkLimit};
+    use crate::circuit::SUB_CIRCUIT_NAMES;
+    use crate::error::Error;
+
+    fn usage(evm: usize, keccak: usize) -> Vec<usize> {
+        let mut usage = vec![0; SUB_CIRCUIT_NAMES.len()];
+        usage[0] = evm;
+        usage[4] = keccak;
+        usage
+    }
+
+    #[test]
+    fn test_plan_chunks() {
+        let usages = [usage(40, 10), usage(50, 10), usage(30, 80), usage(10, 5)];
+        let chunks = plan_chunks(&usages, 100, 10).unwrap();
+        assert_eq!(
+            chunks.iter().map(|c| c.blocks.clone()).collect::<Vec<_>>(),
+            [0..2, 2..4]
+        );
+        assert_eq!(
+            chunks[0].limit,
+            ChunkLimit::Rows {
+                circuit: "evm",
+                rows: 120
+            }
+        );
+        assert_eq!(chunks[0].bottleneck(), ("evm", 90));
+        assert_eq!(chunks[1].limit, ChunkLimit::End);
+        assert_eq!(chunks[1].bottleneck(), ("keccak", 85));
+        assert_eq!(chunks[1].utilization(100), 0.85);
+
+        let chunks = plan_chunks(&usages, 1000, 3).unwrap();
+        assert_eq!(chunks[0].blocks, 0..3);
+        assert_eq!(chunks[0].limit, ChunkLimit::Blocks);
+        assert_eq!(chunks[1].blocks, 3..4);
+
+        assert!(plan_chunks(&[], 100, 10).unwrap().is_empty());
+        assert!(matches!(
+            plan_chunks(&[usage(10, 0), usage(100, 0)], 100, 10),
+            Err(Error::CapacityExceeded { .. })
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bus_mapping::circuit_input_builder::CircuitsParams;
+use once_cell::sync::Lazy;
+use serde_derive::Serialize;
+
+/// Circuit profile of this process, read from `CIRCUIT_PROFILE`. Unlike other env vars, an
+/// unknown profile is not replaced by the default, since its circuits would not match.
+/// Servers should check it with [`circuit_profile_from_env`] at startup.
+pub static CIRCUIT_PROFILE: Lazy<CircuitProfile> =
+    Lazy::new(|| circuit_profile_from_env().unwrap_or_else(|e| panic!("{}", e)));
+
+/// Circuit profile named by `CIRCUIT_PROFILE`, or [`CircuitProfile::K21_MAINNET`] if unset.
+pub fn circuit_profile_from_env() -> std::result::Result<CircuitProfile, String> {
+    match std::env::var("CIRCUIT_PROFILE") {
+        Ok(name) => name.parse(),
+        Err(_) => Ok(CircuitProfile::K21_MAINNET),
+    }
+}
+
+/// Named set of circuit limits. The SuperCircuit bakes `max_txs`, `max_calldata` and
+/// `max_inner_blocks` into const generics, so every profile has its own SuperCircuit type,
+/// see [`with_super_circuit`](crate::with_super_circuit).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct CircuitProfile {
+    pub name: &'static str,
+    /// Default of `DEGREE`.
+    pub degree: usize,
+    pub max_txs: usize,
+    pub max_calldata: usize,
+    pub max_inner_blocks: usize,
+    pub max_rws: usize,
+    pub max_keccak_rows: usize,
+    pub max_exp_steps: usize,
+}
+
+impl CircuitProfile {
+    pub const K21_MAINNET: Self = Self {
+        name: "k21-mainnet",
+        degree: 21,
+        max_txs: 100,
+        max_calldata: 2_000_000,
+        max_inner_blocks: 1,
+        max_rws: 2_000_000,
+        max_keccak_rows: 1_000_000,
+        max_exp_steps: 100_000,
+    };
+
+    pub const K19_DEVNET: Self = Self {
+        name: "k19-devnet",
+        degree: 19,
+        max_txs: 44,
+        max_calldata: 400_000,
+        max_inner_blocks: 100,
+        max_rws: 500_000,
+        max_keccak_rows: 524_000,
+        max_exp_steps: 10_000,
+    };
+
+    /// Batches of up to 16 consecutive blocks with the limits of [`Self::K21_MAINNET`].
+    pub const K21_BATCH: Self = Self {
+        name: "k21-batch",
+        max_inner_blocks: 16,
+        ..Self::K21_MAINNET
+    };
+
+    pub const ALL: [Self; 3] = [Self::K21_MAINNET, Self::K19_DEVNET, Self::K21_BATCH];
+
+    /// Profile whose SuperCircuit has these limits.
+    pub fn of_super_circuit(max_txs: usize, max_calldata: usize, max_inner_blocks: usize) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|profile| {
+                (
+                    profile.max_txs,
+                    profile.max_calldata,
+                    profile.max_inner_blocks,
+                ) == (max_txs, max_calldata, max_inner_blocks)
+            })
+            .expect("SuperCircuit of no profile")
+    }
+
+    /// Name of the SuperCircuit of this profile, which keys its vk, pk and registry entries.
+    /// The profiles other than [`Self::K21_MAINNET`], whose vk is published as "super", add
+    /// their own name so that their keys do not collide.
+    pub fn super_circuit_name(&self) -> String {
+        if *self == Self::K21_MAINNET {
+            "super".to_string()
+        } else {
+            format!("super-{}", self.name)
+        }
+    }
+
+    pub fn circuits_params(&self) -> CircuitsParams {
+        CircuitsParams {
+            max_evm_rows: self.max_rws,
+            max_rws: self.max_rws,
+            max_copy_rows: self.max_rws,
+            max_txs: self.max_txs,
+            max_calldata: self.max_calldata,
+            max_bytecode: self.max_calldata,
+            max_inner_blocks: self.max_inner_blocks,
+            max_keccak_rows: self.max_keccak_rows,
+            max_exp_steps: self.max_exp_steps,
+        }
+    }
+}
+
+impl fmt::Display for CircuitProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl FromStr for CircuitProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|profile| profile.name.eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names = Self::ALL.map(|profile| profile.name);
+                format!("unknown circuit profile {s}, expected one of {names:?}")
+            })
+    }
+}
+
+/// Evaluates `$body` with `$C` bound to the SuperCircuit type of `$profile`.
+#[macro_export]
+macro_rules! with_super_circuit {
+    ($profile:expr, $C:ident => $body:expr) => {{
+        let profile: $crate::circuit::CircuitProfile = $profile;
+        if profile == $crate::circuit::CircuitProfile::K19_DEVNET {
+            type $C = $crate::circuit::DevnetSuperCircuit;
+            $body
+        } else if profile == $crate::circuit::CircuitProfile::K21_BATCH {
+            type $C = $crate::circuit::BatchSuperCircuit;
+            $body
+        } else {
+            type $C = $crate::circuit::SuperCircuit;
+            $body
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CircuitProfile;
+    use crate::circuit::TargetCircuit;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_profile_matches_super_circuit() {
+        let mut names = HashSet::new();
+        for profile in CircuitProfile::ALL {
+            assert_eq!(profile.to_string().parse::<CircuitProfile>(), Ok(profile));
+            let (max_txs, max_calldata, max_inner_blocks) = crate::with_super_circuit!(
+                profile,
+                C => (C::MAX_TXS, C::MAX_CALLDATA, C::MAX_INNER_BLOCKS)
+            );
+            assert_eq!(max_txs, profile.max_txs);
+            assert_eq!(max_calldata, profile.max_calldata);
+            assert_eq!(max_inner_blocks, profile.max_inner_blocks);
+            let name = crate::with_super_circuit!(profile, C => C::name());
+            assert_eq!(name, profile.super_circuit_name());
+            assert!(names.insert(name));
+        }
+        assert!("k20".parse::<CircuitProfile>().is_err());
+    }
+}
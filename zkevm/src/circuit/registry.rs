@@ -0,0 +1,112 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use halo2_proofs::halo2curves::bn256::{Bn256, G1Affine};
+use halo2_proofs::plonk::{keygen_vk, VerifyingKey};
+use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+use once_cell::sync::Lazy;
+use types::eth::BlockTrace;
+
+use crate::circuit::{
+    EvmCircuit, PoseidonCircuit, StateCircuit, TargetCircuit, ZktrieCircuit, CIRCUIT_PROFILE,
+};
+use crate::error::{Error, Result};
+use crate::prover::{Prover, ProverObserver, TargetCircuitProof};
+use crate::verifier::Verifier;
+
+/// Object-safe operations of a [`TargetCircuit`], to pick circuits by name at runtime.
+pub trait DynTargetCircuit: Send + Sync {
+    fn name(&self) -> String;
+    fn mock_prove(
+        &self,
+        block_traces: &[BlockTrace],
+        observer: &Arc<dyn ProverObserver>,
+    ) -> Result<()>;
+    fn prove(&self, prover: &mut Prover, block_traces: &[BlockTrace])
+        -> Result<TargetCircuitProof>;
+    fn verify(&self, verifier: &mut Verifier, proof: &TargetCircuitProof) -> Result<()>;
+    fn estimate_rows(&self, block_traces: &[BlockTrace]) -> Result<usize>;
+    fn keygen_vk(&self, params: &ParamsKZG<Bn256>) -> Result<VerifyingKey<G1Affine>>;
+}
+
+struct Registered<C>(PhantomData<fn() -> C>);
+
+impl<C: TargetCircuit> DynTargetCircuit for Registered<C> {
+    fn name(&self) -> String {
+        C::name()
+    }
+
+    fn mock_prove(
+        &self,
+        block_traces: &[BlockTrace],
+        observer: &Arc<dyn ProverObserver>,
+    ) -> Result<()> {
+        Prover::mock_prove_target_circuit_batch::<C>(block_traces, observer)
+    }
+
+    fn prove(
+        &self,
+        prover: &mut Prover,
+        block_traces: &[BlockTrace],
+    ) -> Result<TargetCircuitProof> {
+        prover.create_target_circuit_proof_batch::<C>(block_traces)
+    }
+
+    fn verify(&self, verifier: &mut Verifier, proof: &TargetCircuitProof) -> Result<()> {
+        verifier.verify_target_circuit_proof::<C>(proof)
+    }
+
+    fn estimate_rows(&self, block_traces: &[BlockTrace]) -> Result<usize> {
+        C::estimate_rows(block_traces)
+    }
+
+    fn keygen_vk(&self, params: &ParamsKZG<Bn256>) -> Result<VerifyingKey<G1Affine>> {
+        keygen_vk(params, &C::empty()).map_err(Error::keygen(C::name()))
+    }
+}
+
+fn register<C: TargetCircuit + 'static>() -> Box<dyn DynTargetCircuit> {
+    Box::new(Registered::<C>(PhantomData))
+}
+
+static TARGET_CIRCUITS: Lazy<Vec<Box<dyn DynTargetCircuit>>> = Lazy::new(|| {
+    vec![
+        register::<EvmCircuit>(),
+        register::<StateCircuit>(),
+        register::<ZktrieCircuit>(),
+        register::<PoseidonCircuit>(),
+        // The SuperCircuit of the active circuit profile.
+        crate::with_super_circuit!(*CIRCUIT_PROFILE, C => register::<C>()),
+    ]
+});
+
+/// Returns the target circuit whose `TargetCircuit::name()` is `name`.
+pub fn target_circuit(name: &str) -> Option<&'static dyn DynTargetCircuit> {
+    TARGET_CIRCUITS
+        .iter()
+        .find(|circuit| circuit.name() == name)
+        .map(|circuit| circuit.as_ref())
+}
+
+/// Names of all registered target circuits.
+pub fn target_circuit_names() -> Vec<String> {
+    TARGET_CIRCUITS
+        .iter()
+        .map(|circuit| circuit.name())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{target_circuit, target_circuit_names};
+
+    #[test]
+    fn test_target_circuit_registry() {
+        let names = target_circuit_names();
+        assert_eq!(names, ["evm", "state", "zktrie", "poseidon", "super"]);
+        for name in names {
+            assert_eq!(target_circuit(&name).unwrap().name(), name);
+        }
+        assert!(target_circuit("agg").is_none());
+    }
+}
@@ -0,0 +1,142 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use halo2_proofs::halo2curves::bn256::Fr;
+use serde_derive::{Deserialize, Serialize};
+use types::eth::BlockTrace;
+use zkevm_circuits::evm_circuit::witness::Block;
+
+use crate::circuit::{block_traces_to_witness_block_with_chain_id, CIRCUIT_PROFILE};
+use crate::error::{Error, Result};
+use crate::version;
+
+/// First bytes of a trace bundle file.
+pub const TRACE_BUNDLE_MAGIC: [u8; 4] = *b"KTRB";
+/// Layout version of trace bundle files, bumped on every incompatible change.
+pub const TRACE_BUNDLE_FORMAT: u32 = 1;
+
+/// Versions the bundled batch was proven with. A bundle is only replayed by the same
+/// versions, so that its witness gives the same circuit as the original run. `chain_id` is
+/// the chain id the batch was built for, which the replay builds it for again.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceBundleHeader {
+    pub prover_version: String,
+    pub zkevm_circuits_version: String,
+    pub circuit_profile: String,
+    pub chain_id: u64,
+}
+
+impl TraceBundleHeader {
+    /// Header of a batch of `chain_id` built by this build and environment.
+    pub fn current(chain_id: u64) -> Self {
+        Self {
+            prover_version: version::as_string(),
+            zkevm_circuits_version: zkevm_circuits::version::as_string(),
+            circuit_profile: CIRCUIT_PROFILE.to_string(),
+            chain_id,
+        }
+    }
+}
+
+/// Block traces of a proven batch saved to disk, to replay mock prove and prove runs.
+///
+/// A replay converts the traces again with bus-mapping in [`TraceBundle::witness_block`].
+/// `witness::Block` and `ZktrieState` of zkevm-circuits implement neither serde nor any
+/// other encoding, and mirroring their fields here would tie the file format to the
+/// internals of one zkevm-circuits version, so the bundle does not hold the witness itself.
+/// The header pins the versions and the chain id the conversion depends on instead.
+/// The file holds [`TRACE_BUNDLE_MAGIC`], [`TRACE_BUNDLE_FORMAT`] as little endian `u32`,
+/// and the json of the bundle.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceBundle {
+    pub header: TraceBundleHeader,
+    pub block_traces: Vec<BlockTrace>,
+}
+
+impl TraceBundle {
+    /// Bundle of `block_traces`, whose witness was built for `chain_id`.
+    pub fn new(block_traces: Vec<BlockTrace>, chain_id: u64) -> Self {
+        Self {
+            header: TraceBundleHeader::current(chain_id),
+            block_traces,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(&TRACE_BUNDLE_MAGIC)?;
+        file.write_all(&TRACE_BUNDLE_FORMAT.to_le_bytes())?;
+        serde_json::to_writer(&mut file, self)?;
+        Ok(())
+    }
+
+    /// Loads a bundle written by [`TraceBundle::save`], failing when its header does not
+    /// match [`TraceBundleHeader::current`] of its chain id.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        if magic != TRACE_BUNDLE_MAGIC {
+            return Err(Error::TraceBundle("not a trace bundle file".to_string()));
+        }
+        let mut format = [0u8; 4];
+        file.read_exact(&mut format)?;
+        let format = u32::from_le_bytes(format);
+        if format != TRACE_BUNDLE_FORMAT {
+            return Err(Error::TraceBundle(format!(
+                "format {format}, but expected {TRACE_BUNDLE_FORMAT}"
+            )));
+        }
+
+        let bundle: Self = serde_json::from_reader(std::io::BufReader::new(file))?;
+        let current = TraceBundleHeader::current(bundle.header.chain_id);
+        if bundle.header != current {
+            return Err(Error::TraceBundle(format!(
+                "built with {:?}, but running {:?}",
+                bundle.header, current
+            )));
+        }
+        Ok(bundle)
+    }
+
+    /// Witness of the bundled batch, built for its chain id.
+    pub fn witness_block(&self) -> Result<Block<Fr>> {
+        block_traces_to_witness_block_with_chain_id(&self.block_traces, self.header.chain_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TraceBundle, TRACE_BUNDLE_FORMAT, TRACE_BUNDLE_MAGIC};
+    use crate::error::Error;
+    use types::eth::BlockTrace;
+
+    #[test]
+    fn test_trace_bundle() {
+        let path = std::env::temp_dir().join(format!("traces_{}.bundle", std::process::id()));
+        let bundle = TraceBundle::new(vec![BlockTrace::default()], 901);
+        bundle.save(&path).unwrap();
+        let loaded = TraceBundle::load(&path).unwrap();
+        assert_eq!(loaded.header, bundle.header);
+        assert_eq!(loaded.header.chain_id, 901);
+        assert_eq!(loaded.block_traces.len(), 1);
+
+        let mut mismatched = bundle.clone();
+        mismatched.header.prover_version = "0.0.0".to_string();
+        mismatched.save(&path).unwrap();
+        assert!(matches!(
+            TraceBundle::load(&path),
+            Err(Error::TraceBundle(_))
+        ));
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes[..4], TRACE_BUNDLE_MAGIC);
+        bytes[4..8].copy_from_slice(&(TRACE_BUNDLE_FORMAT + 1).to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            TraceBundle::load(&path),
+            Err(Error::TraceBundle(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,407 @@
+use eth_types::evm_types::OpcodeId;
+use eth_types::geth_types::DEPOSIT_TX_TYPE;
+use eth_types::{Address, Word, H256};
+use types::eth::BlockTrace;
+
+use crate::error::{Error, Result};
+
+/// Type of EIP-1559 txs.
+const EIP1559_TX_TYPE: u8 = 2;
+
+/// Number of previous blocks whose hashes `BLOCKHASH` can read.
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+
+/// Checks that `block_trace` carries every input the witness needs besides its steps.
+pub fn validate_block_trace(block_trace: &BlockTrace) -> Result<()> {
+    check_block_hashes(block_trace)?;
+    check_gas_prices(block_trace)
+}
+
+/// Checks that `block_trace` and each of its non-deposit txs are of `chain_id`. Deposit txs
+/// may carry a zero chain id.
+pub fn check_chain_id(block_trace: &BlockTrace, chain_id: u64) -> Result<()> {
+    let block = block_trace.header.number.unwrap_or_default().as_u64();
+    if block_trace.chain_id != chain_id.into() {
+        return Err(Error::ChainIdMismatch {
+            location: format!("block {block}"),
+            expected: chain_id,
+            actual: block_trace.chain_id,
+        });
+    }
+    for tx in block_trace.transactions.iter() {
+        if tx.type_ as u64 == DEPOSIT_TX_TYPE && tx.chain_id.is_zero() {
+            continue;
+        }
+        if tx.chain_id != chain_id.into() {
+            return Err(Error::ChainIdMismatch {
+                location: format!("tx {:?} of block {block}", tx.tx_hash),
+                expected: chain_id,
+                actual: tx.chain_id,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the nonce each deposit tx of `block_trace` reports against `state_nonce` of its
+/// sender. Traces that do not report the nonces of deposit txs pass unchecked.
+pub fn check_deposit_nonces(
+    block_trace: &BlockTrace,
+    state_nonce: impl Fn(&Address) -> u64,
+) -> Result<()> {
+    for tx in block_trace.transactions.iter() {
+        if tx.type_ as u64 != DEPOSIT_TX_TYPE {
+            continue;
+        }
+        if let Some(trace) = tx.deposit_nonce {
+            let state = state_nonce(&tx.from);
+            if trace != state {
+                return Err(Error::DepositNonceMismatch {
+                    tx: tx.tx_hash,
+                    trace,
+                    state,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks every block of a batch, and that each block follows the one before it.
+pub fn validate_batch(block_traces: &[BlockTrace]) -> Result<()> {
+    for block_trace in block_traces {
+        validate_block_trace(block_trace)?;
+    }
+    for pair in block_traces.windows(2) {
+        ChainTip::of(&pair[0]).check_next(&pair[1])?;
+    }
+    Ok(())
+}
+
+/// Last block of a batch, that the next block of the batch has to build on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainTip {
+    pub number: u64,
+    pub hash: H256,
+    pub state_root: H256,
+}
+
+impl ChainTip {
+    pub fn of(block_trace: &BlockTrace) -> Self {
+        Self {
+            number: block_trace.header.number.unwrap_or_default().as_u64(),
+            hash: block_trace.header.hash.unwrap_or_default(),
+            state_root: block_trace.storage_trace.root_after,
+        }
+    }
+
+    /// Checks that `next` is the child of this block and starts from its state.
+    pub fn check_next(&self, next: &BlockTrace) -> Result<()> {
+        let block = next.header.number.unwrap_or_default().as_u64();
+        let broken = |reason: String| Err(Error::BlockNotChained { block, reason });
+        if block != self.number + 1 {
+            return broken(format!("previous block is {}", self.number));
+        }
+        if next.header.parent_hash != self.hash {
+            return broken(format!(
+                "parent hash {:?}, but previous block hash is {:?}",
+                next.header.parent_hash, self.hash
+            ));
+        }
+        if next.storage_trace.root_before != self.state_root {
+            return broken(format!(
+                "root before {:?}, but previous root after is {:?}",
+                next.storage_trace.root_before, self.state_root
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Hashes of the blocks before `block_trace`, oldest first, as `BlockHead` takes them.
+pub fn history_hashes(block_trace: &BlockTrace) -> Vec<Word> {
+    block_trace
+        .block_hashes
+        .iter()
+        .flatten()
+        .map(|hash| Word::from_big_endian(hash.as_bytes()))
+        .collect()
+}
+
+/// Every `BLOCKHASH` of one of the previous [`BLOCK_HASH_HISTORY`] blocks needs its hash in
+/// `block_hashes`. Other blocks hash to zero and need nothing.
+fn check_block_hashes(block_trace: &BlockTrace) -> Result<()> {
+    let block = block_trace.header.number.unwrap_or_default().as_u64();
+    let known = block_trace.block_hashes.as_ref().map_or(0, Vec::len) as u64;
+    let requests = block_trace
+        .execution_results
+        .iter()
+        .flat_map(|result| result.exec_steps.iter())
+        .filter(|step| step.op == OpcodeId::BLOCKHASH && step.error.is_none())
+        .filter_map(|step| step.stack.as_ref().and_then(|stack| stack.last()));
+    for requested in requests {
+        if *requested >= Word::from(block) {
+            continue;
+        }
+        let requested = requested.as_u64();
+        let distance = block - requested;
+        if distance <= BLOCK_HASH_HISTORY && distance > known {
+            return Err(Error::MissingBlockHash { block, requested });
+        }
+    }
+    Ok(())
+}
+
+/// Every non-deposit tx has to pay at least the base fee of its block. The gas price of an
+/// EIP-1559 tx is its effective gas price, `min(max_fee, base_fee + max_priority_fee)`, and
+/// the fee caps of other txs are ignored.
+fn check_gas_prices(block_trace: &BlockTrace) -> Result<()> {
+    let base_fee = match block_trace.header.base_fee_per_gas {
+        Some(base_fee) => base_fee,
+        None => return Ok(()),
+    };
+    for tx in block_trace.transactions.iter() {
+        if tx.type_ as u64 == DEPOSIT_TX_TYPE {
+            continue;
+        }
+        let invalid = |reason: String| {
+            Err(Error::InvalidGasPrice {
+                tx: tx.tx_hash,
+                reason,
+            })
+        };
+        if tx.type_ != EIP1559_TX_TYPE {
+            if tx.gas_price < base_fee {
+                return invalid(format!(
+                    "gas price {} below base fee {base_fee}",
+                    tx.gas_price
+                ));
+            }
+            continue;
+        }
+        match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+            (Some(max_fee), Some(max_priority_fee)) => {
+                if max_priority_fee > max_fee {
+                    return invalid(format!(
+                        "max priority fee {max_priority_fee} above max fee {max_fee}"
+                    ));
+                }
+                if max_fee < base_fee {
+                    return invalid(format!("max fee {max_fee} below base fee {base_fee}"));
+                }
+                let effective = max_fee.min(base_fee.saturating_add(max_priority_fee));
+                if tx.gas_price != effective {
+                    return invalid(format!(
+                        "gas price {}, but effective gas price is {effective}",
+                        tx.gas_price
+                    ));
+                }
+            }
+            _ => return invalid("fee caps are missing".to_string()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_chain_id, check_deposit_nonces, validate_batch, validate_block_trace};
+    use crate::error::Error;
+    use eth_types::evm_types::OpcodeId;
+    use eth_types::geth_types::DEPOSIT_TX_TYPE;
+    use eth_types::{Address, Hash, Word, H256};
+    use types::eth::{BlockTrace, ExecStep, ExecutionResult, TransactionTrace};
+
+    fn blockhash_trace(block: u64, requested: u64, hashes: usize) -> BlockTrace {
+        let step = ExecStep {
+            pc: 0,
+            op: OpcodeId::BLOCKHASH,
+            gas: 0,
+            gas_cost: 20,
+            refund: 0,
+            depth: 1,
+            error: None,
+            stack: Some(vec![Word::from(requested)]),
+            memory: None,
+            storage: None,
+            extra_data: None,
+        };
+        let mut trace = BlockTrace::default();
+        trace.header.number = Some(block.into());
+        trace.execution_results.push(ExecutionResult {
+            gas: 0,
+            failed: false,
+            return_value: String::new(),
+            from: None,
+            to: None,
+            account_after: vec![],
+            account_created: None,
+            code_hash: None,
+            byte_code: None,
+            exec_steps: vec![step],
+        });
+        trace.block_hashes = Some(vec![H256::zero(); hashes]);
+        trace
+    }
+
+    #[test]
+    fn test_validate_block_hashes() {
+        validate_block_trace(&blockhash_trace(1000, 999, 1)).unwrap();
+        validate_block_trace(&blockhash_trace(1000, 744, 256)).unwrap();
+        // out of the history, or the block itself
+        validate_block_trace(&blockhash_trace(1000, 743, 0)).unwrap();
+        validate_block_trace(&blockhash_trace(1000, 1000, 0)).unwrap();
+        assert!(matches!(
+            validate_block_trace(&blockhash_trace(1000, 990, 5)),
+            Err(Error::MissingBlockHash {
+                block: 1000,
+                requested: 990
+            })
+        ));
+    }
+
+    fn chained_traces(len: u64) -> Vec<BlockTrace> {
+        (0..len)
+            .map(|i| {
+                let mut trace = BlockTrace::default();
+                trace.header.number = Some((100 + i).into());
+                trace.header.hash = Some(H256::from_low_u64_be(100 + i));
+                trace.header.parent_hash = H256::from_low_u64_be(99 + i);
+                trace.storage_trace.root_before = Hash::from_low_u64_be(i);
+                trace.storage_trace.root_after = Hash::from_low_u64_be(i + 1);
+                trace
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_batch() {
+        validate_batch(&chained_traces(4)).unwrap();
+
+        let mut traces = chained_traces(3);
+        traces.remove(1);
+        assert!(matches!(
+            validate_batch(&traces),
+            Err(Error::BlockNotChained { block: 102, .. })
+        ));
+
+        let mut traces = chained_traces(3);
+        traces[2].header.parent_hash = H256::zero();
+        assert!(validate_batch(&traces).is_err());
+
+        let mut traces = chained_traces(3);
+        traces[1].storage_trace.root_before = Hash::zero();
+        assert!(matches!(
+            validate_batch(&traces),
+            Err(Error::BlockNotChained { block: 101, .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_chain_id() {
+        let tx = |type_: u64, chain_id: u64| TransactionTrace {
+            type_: type_ as u8,
+            chain_id: chain_id.into(),
+            ..Default::default()
+        };
+        let mut trace = BlockTrace {
+            chain_id: 901.into(),
+            ..Default::default()
+        };
+        trace.transactions = vec![tx(DEPOSIT_TX_TYPE, 0), tx(2, 901)];
+        check_chain_id(&trace, 901).unwrap();
+        assert!(matches!(
+            check_chain_id(&trace, 255),
+            Err(Error::ChainIdMismatch { expected: 255, .. })
+        ));
+
+        trace.transactions.push(tx(0, 255));
+        assert!(check_chain_id(&trace, 901).is_err());
+        trace.transactions.pop();
+        trace.transactions.push(tx(DEPOSIT_TX_TYPE, 255));
+        assert!(check_chain_id(&trace, 901).is_err());
+    }
+
+    #[test]
+    fn test_check_deposit_nonces() {
+        let tx = |type_: u64, from: u64, deposit_nonce: Option<u64>| TransactionTrace {
+            type_: type_ as u8,
+            from: Address::from_low_u64_be(from),
+            deposit_nonce,
+            ..Default::default()
+        };
+        let state_nonce = |from: &Address| from.to_low_u64_be() + 10;
+        let mut trace = BlockTrace::default();
+        // legacy deposits and other txs are left to the state
+        trace.transactions = vec![
+            tx(DEPOSIT_TX_TYPE, 1, None),
+            tx(DEPOSIT_TX_TYPE, 2, Some(12)),
+            tx(2, 3, Some(0)),
+        ];
+        check_deposit_nonces(&trace, state_nonce).unwrap();
+
+        trace.transactions.push(tx(DEPOSIT_TX_TYPE, 4, Some(13)));
+        assert!(matches!(
+            check_deposit_nonces(&trace, state_nonce),
+            Err(Error::DepositNonceMismatch {
+                trace: 13,
+                state: 14,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validate_gas_prices() {
+        let tx = |type_: u64, gas_price: u64, fee_caps: Option<(u64, u64)>| TransactionTrace {
+            type_: type_ as u8,
+            gas_price: gas_price.into(),
+            max_fee_per_gas: fee_caps.map(|(max_fee, _)| max_fee.into()),
+            max_priority_fee_per_gas: fee_caps.map(|(_, max_priority_fee)| max_priority_fee.into()),
+            ..Default::default()
+        };
+        let mut trace = BlockTrace::default();
+        trace.header.base_fee_per_gas = Some(100.into());
+        trace.transactions = vec![
+            tx(DEPOSIT_TX_TYPE, 0, None),
+            tx(0, 100, None),
+            tx(1, 120, Some((0, 0))),
+            tx(2, 110, Some((200, 10))),
+            tx(2, 150, Some((150, 80))),
+        ];
+        validate_block_trace(&trace).unwrap();
+
+        // base fee plus priority fee beyond U256 is capped by the max fee
+        let huge_fee_caps = TransactionTrace {
+            gas_price: Word::MAX,
+            max_fee_per_gas: Some(Word::MAX),
+            max_priority_fee_per_gas: Some(Word::MAX),
+            ..tx(2, 0, None)
+        };
+        let mut huge_trace = trace.clone();
+        huge_trace.transactions.push(huge_fee_caps.clone());
+        validate_block_trace(&huge_trace).unwrap();
+
+        for invalid in [
+            tx(0, 99, None),
+            tx(2, 99, Some((99, 0))),
+            tx(2, 100, Some((200, 10))),
+            tx(2, 150, Some((150, 200))),
+            TransactionTrace {
+                gas_price: 110.into(),
+                ..huge_fee_caps
+            },
+            TransactionTrace {
+                max_fee_per_gas: None,
+                ..tx(2, 110, Some((200, 10)))
+            },
+        ] {
+            let mut trace = trace.clone();
+            trace.transactions.push(invalid);
+            assert!(matches!(
+                validate_block_trace(&trace),
+                Err(Error::InvalidGasPrice { .. })
+            ));
+        }
+    }
+}
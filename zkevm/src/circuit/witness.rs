@@ -0,0 +1,308 @@
+use crate::circuit::builder::extend_codedb;
+use crate::circuit::{
+    block_traces_to_witness_block_with_chain_id, calculate_row_usage_of_witness_block,
+    check_chain_id, check_deposit_nonces, history_hashes, validate_block_trace, ChainTip,
+    CIRCUIT_PROFILE, SUB_CIRCUIT_NAMES,
+};
+use crate::error::{Error, Result};
+use bus_mapping::circuit_input_builder::{self, BlockHead, CircuitInputBuilder};
+use bus_mapping::state_db::{Account, CodeDB};
+use eth_types::geth_types::DEPOSIT_TX_TYPE;
+use ethers_core::types::{Bytes, U256};
+use halo2_proofs::halo2curves::bn256::Fr;
+use itertools::Itertools;
+use mpt_zktrie::state::ZktrieState;
+use types::eth::{BlockTrace, EthBlock, StorageTrace};
+use zkevm_circuits::evm_circuit::witness::{block_apply_mpt_state, block_convert, Block};
+
+/// Builds the witness block of a batch one block at a time, so that the row usage after
+/// every block can be checked without replaying the blocks before it.
+///
+/// Accounts and storage slots first seen in a later block are taken from its storage proofs,
+/// the ones touched by earlier blocks keep the state the circuit input builder left them in.
+/// Every block and every non-deposit tx has to be of the chain id the builder is created with.
+///
+/// The row usage of the batch is the row usage of each block on its own, summed over the
+/// blocks as [`plan_batches`](crate::circuit::plan_batches) sums it, so that adding a block
+/// only converts that block.
+///
+/// A builder made with [`WitnessBuilder::new`] reads only the proofs of the added block, and
+/// builds the zktrie state of the batch when [`WitnessBuilder::finalize`] needs it.
+/// [`WitnessBuilder::for_batch`] builds it once up front, and is the better choice when the
+/// blocks are known.
+pub struct WitnessBuilder {
+    builder: Option<CircuitInputBuilder>,
+    /// State of the batch, or `None` while blocks were added since it was last built.
+    zktrie_state: Option<ZktrieState>,
+    /// Storage traces of the added blocks, or `None` when `zktrie_state` was built from the
+    /// whole batch up front.
+    storage_traces: Option<Vec<StorageTrace>>,
+    chain_id: u64,
+    tip: Option<ChainTip>,
+    blocks: usize,
+    row_usage: Vec<usize>,
+}
+
+impl WitnessBuilder {
+    pub fn new(chain_id: u64) -> Self {
+        Self {
+            builder: None,
+            zktrie_state: None,
+            storage_traces: Some(Vec::new()),
+            chain_id,
+            tip: None,
+            blocks: 0,
+            row_usage: vec![0; SUB_CIRCUIT_NAMES.len()],
+        }
+    }
+
+    /// Builder of a batch known up front, with the state of all its blocks.
+    pub fn for_batch(block_traces: &[BlockTrace], chain_id: u64) -> Result<Self> {
+        let storage_traces = block_traces
+            .iter()
+            .map(|b| &b.storage_trace)
+            .collect::<Vec<_>>();
+        Ok(Self {
+            builder: None,
+            zktrie_state: Some(zktrie_state_of(&storage_traces)?),
+            storage_traces: None,
+            chain_id,
+            tip: None,
+            blocks: 0,
+            row_usage: vec![0; SUB_CIRCUIT_NAMES.len()],
+        })
+    }
+
+    /// Chain id the blocks are built for.
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Number of blocks added so far.
+    pub fn num_blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Row usage per sub circuit of `block_trace` on its own, for the chain id of the builder.
+    pub fn block_row_usage(&self, block_trace: &BlockTrace) -> Result<Vec<usize>> {
+        let witness_block = block_traces_to_witness_block_with_chain_id(
+            std::slice::from_ref(block_trace),
+            self.chain_id,
+        )?;
+        calculate_row_usage_of_witness_block(&witness_block)
+    }
+
+    /// Adds the next block of the batch, which has to be the child of the previous one, and
+    /// its row usage to [`WitnessBuilder::row_usage`]. `is_last` tells whether no block
+    /// follows it, since the circuit input builder handles the last tx of a batch differently.
+    pub fn add_block(&mut self, block_trace: &BlockTrace, is_last: bool) -> Result<()> {
+        let usage = self.block_row_usage(block_trace)?;
+        self.add_block_with_usage(block_trace, is_last, &usage)
+    }
+
+    /// Same as [`WitnessBuilder::add_block`], for a block whose `usage` was already taken from
+    /// [`WitnessBuilder::block_row_usage`].
+    pub fn add_block_with_usage(
+        &mut self,
+        block_trace: &BlockTrace,
+        is_last: bool,
+        usage: &[usize],
+    ) -> Result<()> {
+        self.push_block(block_trace, is_last)?;
+        for (acc, rows) in self.row_usage.iter_mut().zip_eq(usage) {
+            *acc = acc.saturating_add(*rows);
+        }
+        Ok(())
+    }
+
+    /// Adds `block_traces` as the last blocks of a batch known to fit. Their row usage is not
+    /// computed, so [`WitnessBuilder::row_usage`] does not count them.
+    pub fn add_blocks(&mut self, block_traces: &[BlockTrace]) -> Result<()> {
+        for (idx, block_trace) in block_traces.iter().enumerate() {
+            self.push_block(block_trace, idx == block_traces.len() - 1)?;
+        }
+        Ok(())
+    }
+
+    /// Row usage per sub circuit of the blocks added with their row usage so far.
+    pub fn row_usage(&self) -> &[usize] {
+        &self.row_usage
+    }
+
+    fn push_block(&mut self, block_trace: &BlockTrace, is_last: bool) -> Result<()> {
+        validate_block_trace(block_trace)?;
+        check_chain_id(block_trace, self.chain_id)?;
+        if let Some(tip) = self.tip {
+            tip.check_next(block_trace)?;
+        }
+        let chain_id = U256::from(self.chain_id);
+
+        // The state of the new block alone holds everything it adds to the batch.
+        let block_state = match self.storage_traces.as_mut() {
+            Some(storage_traces) => {
+                storage_traces.push(block_trace.storage_trace.clone());
+                self.zktrie_state = None;
+                Some(zktrie_state_of(&[&block_trace.storage_trace])?)
+            }
+            None => None,
+        };
+        let zktrie_state = block_state.as_ref().or(self.zktrie_state.as_ref()).unwrap();
+        match self.builder.as_mut() {
+            Some(builder) if block_state.is_some() => {
+                merge_block_state(builder, zktrie_state, block_trace);
+            }
+            Some(_) => {}
+            None => self.builder = Some(start_builder(zktrie_state, chain_id)),
+        }
+        let builder = self.builder.as_mut().unwrap();
+        extend_codedb(
+            &mut builder.code_db,
+            &builder.sdb,
+            std::slice::from_ref(block_trace),
+        )?;
+
+        check_deposit_nonces(block_trace, |from| builder.sdb.get_nonce(from))?;
+        let mut eth_block: EthBlock = block_trace.clone().into();
+        eth_block.transactions.iter_mut().for_each(|transaction| {
+            if let Some(transaction_type) = transaction.transaction_type {
+                // NOTE(chokobole): The nonce of Kroma deposit tx is set to 0 by default.
+                // This causes an error at assert statement in zkevm-circuits.
+                // See gen_begin_tx_ops in bus-mappings/src/evm/opcodes.rs in zkevm-circuits for details.
+                // So here we explicitly set the known nonce from state db to the transaction.
+                // We have an alternative to make go-ethereum or Kroma-node to set nonce explicitly.
+                // But I think this is the fastest way to satisfy requirements.
+                if transaction_type.as_u64() == DEPOSIT_TX_TYPE {
+                    transaction.nonce = U256::from(builder.sdb.get_nonce(&transaction.from));
+                }
+            }
+        });
+
+        let mut geth_trace = Vec::new();
+        for result in &block_trace.execution_results {
+            geth_trace.push(result.into());
+        }
+        let mut header = BlockHead::new(chain_id, history_hashes(block_trace), &eth_block)
+            .map_err(Error::witness)?;
+        // override zeroed minder field with additional "coinbase" field in blocktrace
+        if let Some(address) = block_trace.coinbase.address {
+            header.coinbase = address;
+        }
+
+        builder.block.headers.insert(header.number.as_u64(), header);
+        builder
+            .handle_block_inner(&eth_block, geth_trace.as_slice(), false, is_last)
+            .map_err(Error::witness)?;
+        self.tip = Some(ChainTip::of(block_trace));
+        self.blocks += 1;
+        Ok(())
+    }
+
+    /// Closes the batch and converts it into a witness block.
+    pub fn finalize(mut self) -> Result<Block<Fr>> {
+        self.zktrie_state()?;
+        let zktrie_state = self.zktrie_state.unwrap();
+        let mut builder = match self.builder {
+            Some(builder) => builder,
+            None => start_builder(&zktrie_state, self.chain_id.into()),
+        };
+
+        builder.set_value_ops_call_context_rwc_eor();
+        builder.set_end_block().map_err(Error::witness)?;
+
+        let mut witness_block =
+            block_convert(&builder.block, &builder.code_db).map_err(Error::witness)?;
+        log::debug!(
+            "witness_block.circuits_params {:?}",
+            witness_block.circuits_params
+        );
+
+        block_apply_mpt_state(&mut witness_block, zktrie_state);
+        Ok(witness_block)
+    }
+
+    /// State of the batch, built from the storage traces of the added blocks if needed.
+    fn zktrie_state(&mut self) -> Result<&ZktrieState> {
+        if self.zktrie_state.is_none() {
+            let storage_traces = self.storage_traces.iter().flatten().collect::<Vec<_>>();
+            self.zktrie_state = Some(zktrie_state_of(&storage_traces)?);
+        }
+        Ok(self.zktrie_state.as_ref().unwrap())
+    }
+}
+
+/// State before the first of `storage_traces`, where an earlier proof of an account or
+/// slot wins over a later one.
+fn zktrie_state_of(storage_traces: &[&StorageTrace]) -> Result<ZktrieState> {
+    let old_root = storage_traces
+        .first()
+        .map(|s| s.root_before)
+        .unwrap_or_else(eth_types::Hash::zero);
+    ZktrieState::from_trace_with_additional(
+        old_root,
+        storage_traces.iter().rev().flat_map(|storage_trace| {
+            storage_trace.proofs.iter().flat_map(|kv_map| {
+                kv_map
+                    .iter()
+                    .map(|(k, bts)| (k, bts.iter().map(Bytes::as_ref)))
+            })
+        }),
+        storage_traces.iter().rev().flat_map(|storage_trace| {
+            storage_trace.storage_proofs.iter().flat_map(|(k, kv_map)| {
+                kv_map
+                    .iter()
+                    .map(move |(sk, bts)| (k, sk, bts.iter().map(Bytes::as_ref)))
+            })
+        }),
+        storage_traces
+            .iter()
+            .rev()
+            .flat_map(|storage_trace| storage_trace.deletion_proofs.iter().map(Bytes::as_ref)),
+    )
+    .map_err(Error::witness)
+}
+
+fn start_builder(zktrie_state: &ZktrieState, chain_id: U256) -> CircuitInputBuilder {
+    let mut state_db = zktrie_state.state().clone();
+
+    let (zero_coinbase_exist, _) = state_db.get_account(&Default::default());
+    if !zero_coinbase_exist {
+        state_db.set_account(&Default::default(), Account::zero());
+    }
+
+    let circuit_params = CIRCUIT_PROFILE.circuits_params();
+    let mut builder_block = circuit_input_builder::Block::from_headers(&[], circuit_params);
+    builder_block.chain_id = chain_id;
+    builder_block.prev_state_root = U256::from(zktrie_state.root());
+    CircuitInputBuilder::new(state_db, CodeDB::new(), &builder_block)
+}
+
+/// Copies the accounts and storage slots proven by `block_trace` that earlier blocks did
+/// not touch from `zktrie_state` into the state db of `builder`.
+fn merge_block_state(
+    builder: &mut CircuitInputBuilder,
+    zktrie_state: &ZktrieState,
+    block_trace: &BlockTrace,
+) {
+    let state = zktrie_state.state();
+    let storage_trace = &block_trace.storage_trace;
+    for address in storage_trace.proofs.iter().flat_map(|proofs| proofs.keys()) {
+        if !builder.sdb.get_account(address).0 {
+            let (found, account) = state.get_account(address);
+            if found {
+                builder.sdb.set_account(address, account.clone());
+            }
+        }
+    }
+    for (address, slots) in storage_trace.storage_proofs.iter() {
+        for key in slots.keys() {
+            if builder.sdb.get_storage(address, key).0 {
+                continue;
+            }
+            let (found, value) = state.get_storage(address, key);
+            let (exist, account) = builder.sdb.get_account_mut(address);
+            if found && exist {
+                account.storage.insert(*key, *value);
+            }
+        }
+    }
+}
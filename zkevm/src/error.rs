@@ -0,0 +1,121 @@
+use halo2_proofs::plonk;
+use std::fmt::Display;
+
+/// Errors returned by the prover, the verifier and the witness builder.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The batch does not fit into the circuit, even after truncation.
+    #[error(
+        "circuit capacity not enough: {circuit} needs {rows} rows, but only {max_rows} available"
+    )]
+    CapacityExceeded {
+        circuit: String,
+        rows: usize,
+        max_rows: usize,
+    },
+    #[error("too many blocks: {blocks}, but max is {max_blocks}")]
+    TooManyBlocks { blocks: usize, max_blocks: usize },
+    #[error("circuit not enough: DEGREE = {degree}, less than k needed: {k_needed}")]
+    DegreeInsufficient { degree: usize, k_needed: u32 },
+    /// The trace executes `BLOCKHASH` of a block whose hash it does not carry.
+    #[error("block {block} reads the hash of block {requested}, which the trace lacks")]
+    MissingBlockHash { block: u64, requested: u64 },
+    /// A block or a tx is not of the chain the witness is built for.
+    #[error("chain id mismatch in {location}: expected {expected}, actual {actual}")]
+    ChainIdMismatch {
+        location: String,
+        expected: u64,
+        actual: ethers_core::types::U256,
+    },
+    /// The nonce a trace reports for a deposit tx disagrees with the nonce of its sender in
+    /// the state.
+    #[error("deposit tx {tx:?} has nonce {trace}, but the state has {state}")]
+    DepositNonceMismatch {
+        tx: ethers_core::types::H256,
+        trace: u64,
+        state: u64,
+    },
+    /// A tx pays a gas price its fee caps or the base fee of its block do not allow.
+    #[error("invalid gas price of tx {tx:?}: {reason}")]
+    InvalidGasPrice {
+        tx: ethers_core::types::H256,
+        reason: String,
+    },
+    /// A block of a batch does not build on the block before it.
+    #[error("block {block} does not follow the previous block of the batch: {reason}")]
+    BlockNotChained { block: u64, reason: String },
+    /// The block traces could not be turned into a witness block.
+    #[error("failed to convert traces into witness: {0}")]
+    WitnessConversion(String),
+    #[error("params mismatch: {0}")]
+    ParamsMismatch(String),
+    /// The agg params were released to stay within the memory budget, and the prover has no
+    /// directory to load them from again.
+    #[error("agg params are released and cannot be reloaded without an agg params dir")]
+    AggParamsReleased,
+    #[error("failed to generate keys of {circuit} circuit: {source}")]
+    Keygen {
+        circuit: String,
+        #[source]
+        source: plonk::Error,
+    },
+    #[error("failed to create proof of {circuit} circuit: {source}")]
+    Proof {
+        circuit: String,
+        #[source]
+        source: plonk::Error,
+    },
+    #[error("mock prove of {circuit} circuit failed: {failures}")]
+    MockProve { circuit: String, failures: String },
+    #[error("failed to verify proof of {circuit} circuit: {source}")]
+    Verification {
+        circuit: String,
+        #[source]
+        source: plonk::Error,
+    },
+    #[error("memory budget exceeded: {needed} bytes needed, but budget is {budget} bytes")]
+    MemoryBudgetExceeded { needed: u64, budget: u64 },
+    #[error("vk of {0} circuit not found")]
+    VkNotFound(String),
+    #[error("vk digest mismatch of {circuit} circuit: expected {expected}, actual {actual}")]
+    VkDigestMismatch {
+        circuit: String,
+        expected: String,
+        actual: String,
+    },
+    /// A saved trace bundle cannot be replayed by this build.
+    #[error("invalid trace bundle: {0}")]
+    TraceBundle(String),
+    #[error("invalid proof: {0}")]
+    InvalidProof(String),
+    /// The requested combination of options is not supported, e.g. aggregating SHPLONK proofs.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+impl Error {
+    pub(crate) fn witness(err: impl Display) -> Self {
+        Error::WitnessConversion(err.to_string())
+    }
+
+    pub(crate) fn keygen(circuit: impl Into<String>) -> impl FnOnce(plonk::Error) -> Self {
+        let circuit = circuit.into();
+        move |source| Error::Keygen { circuit, source }
+    }
+
+    pub(crate) fn proof(circuit: impl Into<String>) -> impl FnOnce(plonk::Error) -> Self {
+        let circuit = circuit.into();
+        move |source| Error::Proof { circuit, source }
+    }
+
+    pub(crate) fn verification(circuit: impl Into<String>) -> impl FnOnce(plonk::Error) -> Self {
+        let circuit = circuit.into();
+        move |source| Error::Verification { circuit, source }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -3,6 +3,9 @@ use std::{
     path::PathBuf,
 };
 
+use ethers_core::abi::{self, Token};
+use ethers_core::types::U256;
+use ethers_core::utils::id;
 use halo2_proofs::{
     halo2curves::bn256::{Bn256, Fq, Fr, G1Affine},
     plonk::VerifyingKey,
@@ -10,8 +13,11 @@ use halo2_proofs::{
     SerdeFormat,
 };
 use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
 use zkevm_circuits::tx_circuit::PrimeField;
 
+use crate::error::{Error, Result};
+
 pub fn serialize_fr(f: &Fr) -> Vec<u8> {
     f.to_bytes().to_vec()
 }
@@ -44,6 +50,73 @@ pub fn deserialize_fr_tensor(l4_buf: Vec<Vec<Vec<Vec<u8>>>>) -> Vec<Vec<Vec<Fr>>
     l4_buf.into_iter().map(deserialize_fr_matrix).collect()
 }
 
+/// Encodes instances followed by the proof as calldata of an EVM verifier. Each instance
+/// is written as a 32-byte big-endian word.
+pub fn encode_calldata(instances: &[Vec<Fr>], proof: &[u8]) -> Vec<u8> {
+    instances
+        .iter()
+        .flatten()
+        .flat_map(|value| {
+            let mut bytes = value.to_bytes();
+            bytes.reverse();
+            bytes
+        })
+        .chain(proof.iter().copied())
+        .collect()
+}
+
+/// Function of the generated verifier contract, called by the Kroma colosseum with the agg
+/// proof and the final pair.
+pub const VERIFIER_FUNCTION: &str = "verify(uint256[],uint256[])";
+
+/// Reads `buf` as 32-byte little-endian words, as written by the agg transcript and
+/// `serialize_verify_circuit_final_pair`, into uint256s.
+fn le_words_to_uint256s(buf: &[u8]) -> Result<Vec<U256>> {
+    if buf.len() % 32 != 0 {
+        return Err(Error::InvalidProof(format!(
+            "len {} is not a multiple of 32",
+            buf.len()
+        )));
+    }
+    Ok(buf.chunks(32).map(U256::from_little_endian).collect())
+}
+
+/// Converts the 32-byte little-endian words of `buf` to big-endian, as the verifier
+/// contract reads them.
+pub fn le_words_to_be(buf: &[u8]) -> Result<Vec<u8>> {
+    Ok(le_words_to_uint256s(buf)?
+        .into_iter()
+        .flat_map(|word| {
+            let mut be = [0u8; 32];
+            word.to_big_endian(&mut be);
+            be
+        })
+        .collect())
+}
+
+/// ABI-encodes an agg proof and its final pair as the `(uint256[] proof, uint256[]
+/// target_circuit_final_pair)` arguments of [`VERIFIER_FUNCTION`], prefixed with its
+/// selector if `with_selector`.
+pub fn encode_verifier_calldata(
+    proof: &[u8],
+    final_pair: &[u8],
+    with_selector: bool,
+) -> Result<Vec<u8>> {
+    let to_token = |buf: &[u8]| -> Result<Token> {
+        Ok(Token::Array(
+            le_words_to_uint256s(buf)?
+                .into_iter()
+                .map(Token::Uint)
+                .collect(),
+        ))
+    };
+    let args = abi::encode(&[to_token(proof)?, to_token(final_pair)?]);
+    if !with_selector {
+        return Ok(args);
+    }
+    Ok(id(VERIFIER_FUNCTION).into_iter().chain(args).collect())
+}
+
 pub fn serialize_instance(instance: &[Vec<Fr>]) -> Vec<u8> {
     let instances_for_serde = serialize_fr_matrix(instance);
 
@@ -109,6 +182,10 @@ pub fn load_verify_circuit_instance(folder: &mut PathBuf) -> Vec<u8> {
     read_file(folder, "verify_circuit_instance.data")
 }
 
+pub fn load_verify_circuit_final_pair(folder: &mut PathBuf) -> Vec<u8> {
+    read_file(folder, "verify_circuit_final_pair.data")
+}
+
 pub fn load_verify_circuit_proof(folder: &mut PathBuf) -> Vec<u8> {
     read_file(folder, "verify_circuit_proof.data")
 }
@@ -127,6 +204,11 @@ pub fn serialize_vk(vk: &VerifyingKey<G1Affine>) -> Vec<u8> {
     result
 }
 
+/// Hex encoded sha256 digest of a serialized vk, as published for verifiers.
+pub fn vk_digest(vk: &[u8]) -> String {
+    hex::encode(Sha256::digest(vk))
+}
+
 pub fn write_verify_circuit_vk(folder: &mut PathBuf, verify_circuit_vk: &[u8]) {
     folder.push("verify_circuit.vkey");
     let mut fd = std::fs::File::create(folder.as_path()).unwrap();
@@ -224,3 +306,34 @@ pub fn load_instances_flat(buf: &[u8]) -> Vec<Vec<Vec<Fr>>> {
 
     vec![vec![ret]]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_verifier_calldata, VERIFIER_FUNCTION};
+    use ethers_core::utils::id;
+
+    #[test]
+    fn test_encode_verifier_calldata() {
+        let mut proof = vec![0u8; 64];
+        proof[0] = 1;
+        proof[32] = 2;
+        let final_pair = vec![3u8; 32];
+
+        let calldata = encode_verifier_calldata(&proof, &final_pair, true).unwrap();
+        assert_eq!(calldata[..4], id(VERIFIER_FUNCTION));
+        let words = calldata[4..].chunks(32).collect::<Vec<_>>();
+        assert_eq!(words.len(), 7);
+        // offsets of both arrays, then the length and the big-endian words of each
+        assert_eq!(words[0][31], 0x40);
+        assert_eq!(words[1][31], 0xa0);
+        assert_eq!(words[2][31], 2);
+        assert_eq!(words[3][31], 1);
+        assert_eq!(words[4][31], 2);
+        assert_eq!(words[5][31], 1);
+        assert_eq!(words[6], [3u8; 32]);
+
+        let args = encode_verifier_calldata(&proof, &final_pair, false).unwrap();
+        assert_eq!(args, calldata[4..]);
+        assert!(encode_verifier_calldata(&proof[1..], &final_pair, false).is_err());
+    }
+}
@@ -1,9 +1,15 @@
 pub mod circuit;
+pub mod error;
 pub mod io;
 pub mod prover;
+pub mod public_inputs;
+pub mod solidity;
 pub mod utils;
 pub mod verifier;
 pub mod version;
+pub mod vk_registry;
+
+pub use crate::error::{Error, Result};
 
 pub mod proof {
     use crate::prover::AggCircuitProof;
@@ -1,14 +1,19 @@
 use crate::circuit::{
-    block_traces_to_witness_block, check_batch_capacity, SuperCircuit, TargetCircuit, AGG_DEGREE,
-    DEGREE,
+    check_batch_capacity, SuperCircuit, TargetCircuit, TraceBundle, AGG_DEGREE, CHAIN_ID,
+    CIRCUIT_PROFILE, DEGREE,
 };
+use crate::error::{Error, Result};
 use crate::io::{
-    deserialize_fr_matrix, load_instances, serialize_fr_tensor, serialize_instance,
-    serialize_verify_circuit_final_pair, serialize_vk, write_verify_circuit_final_pair,
-    write_verify_circuit_instance, write_verify_circuit_proof, write_verify_circuit_vk,
+    deserialize_fr_matrix, encode_calldata, encode_verifier_calldata, le_words_to_be,
+    load_instances, serialize_fr_tensor, serialize_instance, serialize_verify_circuit_final_pair,
+    serialize_vk, write_file, write_verify_circuit_final_pair, write_verify_circuit_instance,
+    write_verify_circuit_proof, write_verify_circuit_proof_be, write_verify_circuit_vk,
 };
-use crate::utils::{load_or_create_params, load_seed, metric_of_witness_block, read_env_var};
-use anyhow::{bail, Error};
+use crate::solidity::generate_verifier;
+use crate::utils::{
+    load_kzg_params, load_or_create_params, load_seed, metric_of_witness_block, read_env_var,
+};
+use crate::vk_registry::AGG_VK_NAME;
 #[cfg(feature = "tachyon")]
 use halo2_proofs::{
     bn254::{
@@ -17,7 +22,6 @@ use halo2_proofs::{
     },
     consts::TranscriptType,
     plonk::tachyon::create_proof as create_tachyon_proof,
-    poly::commitment::Params,
     xor_shift_rng::XORShiftRng,
 };
 use halo2_proofs::{
@@ -25,41 +29,62 @@ use halo2_proofs::{
     halo2curves::bn256::{Bn256, Fr, G1Affine},
     plonk::{keygen_pk, keygen_pk2, keygen_vk, ProvingKey, VerifyingKey},
     poly::{
-        commitment::ParamsProver,
+        commitment::{Params, ParamsProver},
         kzg::commitment::{KZGCommitmentScheme, ParamsKZG},
     },
     SerdeFormat,
 };
 #[cfg(not(feature = "tachyon"))]
 use halo2_proofs::{
-    plonk::create_proof,
-    poly::kzg::multiopen::ProverGWC,
-    transcript::{Challenge255, PoseidonWrite},
+    plonk::{create_proof, Circuit},
+    poly::kzg::multiopen::{ProverGWC, ProverSHPLONK},
+    transcript::{Challenge255, PoseidonWrite, TranscriptWrite},
 };
 use halo2_snark_aggregator_circuit::verify_circuit::{
     final_pair_to_instances, Halo2CircuitInstance, Halo2CircuitInstances, Halo2VerifierCircuit,
     Halo2VerifierCircuits, SingleProofWitness,
 };
-use halo2_snark_aggregator_solidity::{MultiCircuitSolidityGenerate, SolidityGenerate};
 use log::info;
 use once_cell::sync::Lazy;
-use rand::SeedableRng;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Cursor;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use types::{base64, eth::BlockTrace};
+use zkevm_circuits::evm_circuit::witness::Block;
 
 #[cfg(not(feature = "tachyon"))]
 use halo2_snark_aggregator_api::transcript::sha::ShaWrite;
 #[cfg(not(feature = "tachyon"))]
-use rand_xorshift::XorShiftRng;
+use sha3::Keccak256;
 
 #[cfg(target_os = "linux")]
 extern crate procfs;
 
+mod memory;
+mod metadata;
+mod observer;
+
+pub use self::memory::{current_rss, peak_rss, MEMORY_BUDGET};
+use self::memory::{estimate_agg_pk_bytes, ReleasePlan};
+pub use self::metadata::{MultiopenScheme, ProofMetadata, RngMode, TranscriptKind};
+use self::observer::PhaseGuard;
+pub use self::observer::{LogObserver, ProverObserver, ProverPhase};
+
 pub static OPT_MEM: Lazy<bool> = Lazy::new(|| read_env_var("OPT_MEM", false));
 pub static MOCK_PROVE: Lazy<bool> = Lazy::new(|| read_env_var("MOCK_PROVE", false));
+/// Save the block traces of every target proof into `debug_dir`, see [`TraceBundle`].
+pub static DUMP_TRACE_BUNDLE: Lazy<bool> = Lazy::new(|| read_env_var("DUMP_TRACE_BUNDLE", false));
+pub static TARGET_MULTIOPEN: Lazy<MultiopenScheme> =
+    Lazy::new(|| read_env_var("TARGET_MULTIOPEN", MultiopenScheme::Gwc));
+pub static AGG_MULTIOPEN: Lazy<MultiopenScheme> =
+    Lazy::new(|| read_env_var("AGG_MULTIOPEN", MultiopenScheme::Gwc));
+pub static TARGET_TRANSCRIPT: Lazy<TranscriptKind> =
+    Lazy::new(|| read_env_var("TARGET_TRANSCRIPT", TranscriptKind::Poseidon));
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct TargetCircuitProof {
@@ -72,9 +97,11 @@ pub struct TargetCircuitProof {
     pub vk: Vec<u8>,
     pub proved_block_count: usize,
     pub original_block_count: usize,
+    #[serde(default)]
+    pub metadata: ProofMetadata,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 pub struct AggCircuitProof {
     #[serde(with = "base64")]
     pub proof: Vec<u8>,
@@ -89,6 +116,27 @@ pub struct AggCircuitProof {
     pub vk: Vec<u8>,
 
     pub block_count: usize,
+
+    #[serde(default)]
+    pub metadata: ProofMetadata,
+}
+
+impl TargetCircuitProof {
+    /// Returns the calldata for verifying this proof with an EVM verifier. Only proofs with
+    /// a Keccak transcript can be verified on-chain without aggregation.
+    pub fn evm_calldata(&self) -> Result<Vec<u8>> {
+        if self.metadata.transcript != Some(TranscriptKind::Keccak) {
+            return Err(Error::Unsupported(format!(
+                "evm calldata of {} proof without keccak transcript",
+                self.name
+            )));
+        }
+        let instances: Vec<Vec<Vec<u8>>> = serde_json::from_reader(&self.instance[..])?;
+        Ok(encode_calldata(
+            &deserialize_fr_matrix(instances),
+            &self.proof,
+        ))
+    }
 }
 
 pub struct ProvedCircuit {
@@ -101,11 +149,23 @@ pub struct ProvedCircuit {
 }
 
 impl AggCircuitProof {
+    /// Calldata of the generated verifier contract, see [`encode_verifier_calldata`].
+    pub fn verifier_calldata(&self, with_selector: bool) -> Result<Vec<u8>> {
+        encode_verifier_calldata(&self.proof, &self.final_pair, with_selector)
+    }
+
     pub fn write_to_dir(&self, out_dir: &mut PathBuf) {
         write_verify_circuit_final_pair(out_dir, &self.final_pair);
         write_verify_circuit_instance(out_dir, &self.instance);
         write_verify_circuit_proof(out_dir, &self.proof);
         write_verify_circuit_vk(out_dir, &self.vk);
+        match (le_words_to_be(&self.proof), self.verifier_calldata(true)) {
+            (Ok(proof_be), Ok(calldata)) => {
+                write_verify_circuit_proof_be(out_dir, &proof_be);
+                write_file(out_dir, "verify_circuit_calldata.data", &calldata);
+            }
+            (Err(e), _) | (_, Err(e)) => log::error!("failed to encode verifier calldata: {}", e),
+        }
 
         out_dir.push("full_proof.data");
         let mut fd = std::fs::File::create(out_dir.as_path()).unwrap();
@@ -114,132 +174,285 @@ impl AggCircuitProof {
     }
 }
 
-#[derive(Debug)]
+/// Creates a KZG proof of `circuit` with the given multiopen scheme into `transcript`.
+#[cfg(not(feature = "tachyon"))]
+fn create_kzg_proof<ConcreteCircuit, T>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: ConcreteCircuit,
+    instances: &[&[&[Fr]]],
+    multiopen: MultiopenScheme,
+    rng: impl RngCore,
+    transcript: &mut T,
+) -> std::result::Result<(), halo2_proofs::plonk::Error>
+where
+    ConcreteCircuit: Circuit<Fr>,
+    T: TranscriptWrite<G1Affine, Challenge255<G1Affine>>,
+{
+    match multiopen {
+        MultiopenScheme::Gwc => create_proof::<KZGCommitmentScheme<_>, ProverGWC<_>, _, _, _, _>(
+            params,
+            pk,
+            &[circuit],
+            instances,
+            rng,
+            transcript,
+        ),
+        MultiopenScheme::Shplonk => create_proof::<
+            KZGCommitmentScheme<_>,
+            ProverSHPLONK<_>,
+            _,
+            _,
+            _,
+            _,
+        >(params, pk, &[circuit], instances, rng, transcript),
+    }
+}
+
+/// Randomness source used for the blinding factors of proofs.
+pub trait ProverRng: RngCore + CryptoRng + Send {}
+
+impl<R: RngCore + CryptoRng + Send> ProverRng for R {}
+
 pub struct Prover {
     pub params: ParamsKZG<Bn256>,
     pub agg_params: Option<ParamsKZG<Bn256>>,
-    #[cfg(not(feature = "tachyon"))]
-    pub rng: XorShiftRng,
-    #[cfg(feature = "tachyon")]
-    pub rng: XORShiftRng,
+    /// A [`ProverRng`], except for the xorshift rng of seeded provers.
+    rng: Box<dyn RngCore + Send>,
+    rng_mode: RngMode,
 
     pub target_circuit_pks: HashMap<String, ProvingKey<G1Affine>>,
     pub agg_pk: Option<ProvingKey<G1Affine>>,
     pub debug_dir: String,
+    pub observer: Arc<dyn ProverObserver>,
+    /// Chain id every proven block and non-deposit tx has to be of, [`CHAIN_ID`] by default.
+    pub chain_id: u64,
+    /// Multiopen scheme of target circuit proofs. Only GWC proofs can be aggregated.
+    pub target_multiopen: MultiopenScheme,
+    /// Multiopen scheme of the aggregation proof. Only GWC proofs have a Solidity verifier.
+    pub agg_multiopen: MultiopenScheme,
+    /// Transcript of target circuit proofs. Keccak proofs are verified directly on-chain
+    /// and cannot be aggregated.
+    pub target_transcript: TranscriptKind,
+    /// Memory budget in bytes. When set, the prover releases the target pk, the agg params
+    /// and witnesses between phases as needed, and fails early if the agg pk cannot fit.
+    pub memory_budget: Option<u64>,
+    /// Directory the agg params are reloaded from once released. Without it, a prover that
+    /// released its agg params cannot create another agg proof.
+    pub agg_params_dir: Option<String>,
+    /// Degree of the agg params while they are released.
+    released_agg_degree: Option<u32>,
     //pub target_circuit_vks: HashMap<String, ProvingKey<G1Affine>>,
 }
 
+impl std::fmt::Debug for Prover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Prover")
+            .field("params", &self.params)
+            .field("agg_params", &self.agg_params)
+            .field("rng_mode", &self.rng_mode)
+            .field("target_circuit_pks", &self.target_circuit_pks)
+            .field("agg_pk", &self.agg_pk)
+            .field("debug_dir", &self.debug_dir)
+            .field("chain_id", &self.chain_id)
+            .field("target_multiopen", &self.target_multiopen)
+            .field("agg_multiopen", &self.agg_multiopen)
+            .field("target_transcript", &self.target_transcript)
+            .field("memory_budget", &self.memory_budget)
+            .field("agg_params_dir", &self.agg_params_dir)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Prover {
-    #[cfg(not(feature = "tachyon"))]
-    pub fn new(
+    /// Creates a prover drawing its blinding randomness from the operating system.
+    pub fn new(params: ParamsKZG<Bn256>, agg_params: Option<ParamsKZG<Bn256>>) -> Self {
+        Self::with_rng_mode(params, agg_params, Box::new(OsRng), RngMode::Os)
+    }
+
+    fn with_rng_mode(
         params: ParamsKZG<Bn256>,
         agg_params: Option<ParamsKZG<Bn256>>,
-        rng: XorShiftRng,
+        rng: Box<dyn RngCore + Send>,
+        rng_mode: RngMode,
     ) -> Self {
+        if let Some(agg_params) = &agg_params {
+            log::info!("params g2 {:?} s_g2 {:?}", params.g2(), params.s_g2());
+            debug_assert_eq!(params.s_g2(), agg_params.s_g2());
+            debug_assert_eq!(params.g2(), agg_params.g2());
+        }
         Self {
             params,
             agg_params,
             rng,
+            rng_mode,
             target_circuit_pks: Default::default(),
             agg_pk: None,
             debug_dir: Default::default(),
+            observer: Arc::new(LogObserver),
+            chain_id: *CHAIN_ID,
+            target_multiopen: *TARGET_MULTIOPEN,
+            agg_multiopen: *AGG_MULTIOPEN,
+            target_transcript: *TARGET_TRANSCRIPT,
+            memory_budget: *MEMORY_BUDGET,
+            agg_params_dir: None,
+            released_agg_degree: None,
         }
     }
 
-    #[cfg(feature = "tachyon")]
-    pub fn new(
-        params: ParamsKZG<Bn256>,
-        agg_params: Option<ParamsKZG<Bn256>>,
-        rng: XORShiftRng,
-    ) -> Self {
-        Self {
-            params,
-            agg_params,
-            rng,
-            target_circuit_pks: Default::default(),
-            agg_pk: None,
-            debug_dir: Default::default(),
+    fn tick(&self, desc: &str) {
+        self.observer
+            .on_memory_sample(desc, current_rss().unwrap_or_default());
+    }
+
+    /// Bytes the agg pk is expected to take, or 0 if it is already generated.
+    fn pending_agg_pk_bytes(&self) -> u64 {
+        if self.agg_pk.is_some() {
+            0
+        } else {
+            estimate_agg_pk_bytes(*AGG_DEGREE)
+        }
+    }
+
+    fn release_plan(&self, pending: u64) -> ReleasePlan {
+        ReleasePlan::new(
+            self.memory_budget,
+            current_rss().unwrap_or_default(),
+            pending,
+            *OPT_MEM,
+        )
+    }
+
+    /// Drops the agg params to save memory. They are loaded again from `agg_params_dir` by
+    /// the next agg proof.
+    fn release_agg_params(&mut self) {
+        if let Some(agg_params) = self.agg_params.take() {
+            self.tick("before release agg params");
+            self.released_agg_degree = Some(agg_params.k());
+            drop(agg_params);
+            self.tick("after release agg params");
         }
     }
 
-    fn tick(desc: &str) {
-        #[cfg(target_os = "linux")]
-        let memory = match procfs::Meminfo::new() {
-            Ok(m) => m.mem_total - m.mem_free,
-            Err(_) => 0,
+    /// Loads the agg params again if they were released.
+    fn reload_agg_params(&mut self) -> Result<()> {
+        let degree = match self.released_agg_degree {
+            Some(degree) if self.agg_params.is_none() => degree,
+            _ => return Ok(()),
         };
-        #[cfg(not(target_os = "linux"))]
-        let memory = 0;
-        log::debug!(
-            "memory usage when {}: {:?}GB",
-            desc,
-            memory / 1024 / 1024 / 1024
-        );
+        let dir = self
+            .agg_params_dir
+            .as_deref()
+            .ok_or(Error::AggParamsReleased)?;
+        self.tick("before reload agg params");
+        self.agg_params = Some(load_kzg_params(dir, degree as usize)?);
+        self.released_agg_degree = None;
+        self.tick("after reload agg params");
+        Ok(())
     }
 
-    fn init_pk<C: TargetCircuit>(&mut self, circuit: &<C as TargetCircuit>::Inner) {
-        Self::tick(&format!("before init pk of {}", C::name()));
-        let pk = keygen_pk2(&self.params, circuit)
-            .unwrap_or_else(|e| panic!("failed to generate {} pk: {:?}", C::name(), e));
-        self.target_circuit_pks.insert(C::name(), pk);
-        Self::tick(&format!("after init pk of {}", C::name()));
+    /// Fails if the agg params are missing and cannot be reloaded, or if the agg pk cannot
+    /// fit into the memory budget even after releasing everything else the prover holds.
+    fn check_agg_ready(&mut self) -> Result<()> {
+        self.reload_agg_params()?;
+        if self.agg_params.is_none() {
+            return Err(Error::ParamsMismatch(
+                "agg params are not loaded".to_string(),
+            ));
+        }
+        let needed = self.pending_agg_pk_bytes();
+        match self.memory_budget {
+            Some(budget) if needed > budget => Err(Error::MemoryBudgetExceeded { needed, budget }),
+            _ => Ok(()),
+        }
     }
 
-    #[cfg(not(feature = "tachyon"))]
-    pub fn from_params_and_rng(
-        params: ParamsKZG<Bn256>,
-        agg_params: Option<ParamsKZG<Bn256>>,
-        rng: XorShiftRng,
-    ) -> Self {
-        Self::new(params, agg_params, rng)
+    fn init_pk<C: TargetCircuit>(&mut self, circuit: &<C as TargetCircuit>::Inner) -> Result<()> {
+        let _phase = PhaseGuard::new(&self.observer, ProverPhase::TargetKeygen, &C::name());
+        self.tick(&format!("before init pk of {}", C::name()));
+        let pk = keygen_pk2(&self.params, circuit).map_err(Error::keygen(C::name()))?;
+        self.target_circuit_pks.insert(C::name(), pk);
+        self.tick(&format!("after init pk of {}", C::name()));
+        Ok(())
     }
 
-    #[cfg(feature = "tachyon")]
-    pub fn from_params_and_rng(
+    /// Creates a prover with an injected randomness source.
+    pub fn from_params_and_rng<R: ProverRng + 'static>(
         params: ParamsKZG<Bn256>,
         agg_params: Option<ParamsKZG<Bn256>>,
-        rng: XORShiftRng,
+        rng: R,
     ) -> Self {
-        Self::new(params, agg_params, rng)
+        Self::with_rng_mode(params, agg_params, Box::new(rng), RngMode::Injected)
     }
 
+    /// Creates a prover seeded with a fixed value. For tests only: every proof created by
+    /// such a prover reuses the same blinding randomness.
     pub fn from_params_and_seed(
         params: ParamsKZG<Bn256>,
         agg_params: Option<ParamsKZG<Bn256>>,
         seed: [u8; 16],
     ) -> Self {
-        if let Some(agg_params) = &agg_params {
-            log::info!("params g2 {:?} s_g2 {:?}", params.g2(), params.s_g2());
-            debug_assert_eq!(params.s_g2(), agg_params.s_g2());
-            debug_assert_eq!(params.g2(), agg_params.g2());
-        }
-        #[cfg(not(feature = "tachyon"))]
+        log::warn!("prover uses deterministic randomness, which is for tests only");
         let rng = XorShiftRng::from_seed(seed);
-        #[cfg(feature = "tachyon")]
-        let rng = XORShiftRng::from_seed(seed);
+        Self::with_rng_mode(params, agg_params, Box::new(rng), RngMode::Deterministic)
+    }
 
-        Self::from_params_and_rng(params, agg_params, rng)
+    pub fn from_fpath(params_fpath: &str) -> Self {
+        let params = load_or_create_params(params_fpath, *DEGREE).expect("failed to init params");
+        let agg_params =
+            load_or_create_params(params_fpath, *AGG_DEGREE).expect("failed to init params");
+        let mut prover = Self::new(params, Some(agg_params));
+        prover.agg_params_dir = Some(params_fpath.to_string());
+        prover
     }
 
-    pub fn from_fpath(params_fpath: &str, seed_fpath: &str) -> Self {
+    /// Same as [`Prover::from_fpath`], but seeded from `seed_fpath`. For tests only.
+    pub fn from_fpath_and_seed(params_fpath: &str, seed_fpath: &str) -> Self {
         let params = load_or_create_params(params_fpath, *DEGREE).expect("failed to init params");
         let agg_params =
             load_or_create_params(params_fpath, *AGG_DEGREE).expect("failed to init params");
         let seed = load_seed(seed_fpath).expect("failed to init rng");
-        Self::from_params_and_seed(params, Some(agg_params), seed)
+        let mut prover = Self::from_params_and_seed(params, Some(agg_params), seed);
+        prover.agg_params_dir = Some(params_fpath.to_string());
+        prover
+    }
+
+    pub fn rng_mode(&self) -> RngMode {
+        self.rng_mode
+    }
+
+    fn proof_metadata(
+        &self,
+        multiopen: MultiopenScheme,
+        transcript: TranscriptKind,
+    ) -> ProofMetadata {
+        ProofMetadata {
+            rng_mode: self.rng_mode,
+            multiopen,
+            transcript: Some(transcript),
+        }
+    }
+
+    /// Tachyon only accepts its own xorshift rng, so each proof gets one freshly seeded
+    /// from the prover's randomness source.
+    #[cfg(feature = "tachyon")]
+    fn tachyon_rng(&mut self) -> XORShiftRng {
+        let mut seed = [0u8; 16];
+        self.rng.fill_bytes(&mut seed);
+        XORShiftRng::from_seed(seed)
     }
 
     pub fn debug_load_proved_circuit<C: TargetCircuit>(
         &mut self,
         v: Option<&mut crate::verifier::Verifier>,
-    ) -> anyhow::Result<ProvedCircuit> {
+    ) -> Result<ProvedCircuit> {
         assert!(!self.debug_dir.is_empty());
         log::debug!("debug_load_proved_circuit {}", C::name());
         let file_name = format!("{}/{}_proof.json", self.debug_dir, C::name());
         let file = std::fs::File::open(file_name)?;
         let proof: TargetCircuitProof = serde_json::from_reader(file)?;
         if let Some(v) = v {
-            v.verify_target_circuit_proof::<C>(&proof).unwrap();
+            v.verify_target_circuit_proof::<C>(&proof)?;
         }
         self.convert_target_proof::<C>(&proof)
     }
@@ -247,7 +460,7 @@ impl Prover {
     pub fn prove_circuit<C: TargetCircuit>(
         &mut self,
         block_traces: &[BlockTrace],
-    ) -> anyhow::Result<ProvedCircuit> {
+    ) -> Result<ProvedCircuit> {
         let proof = self.create_target_circuit_proof_batch::<C>(block_traces)?;
         self.convert_target_proof::<C>(&proof)
     }
@@ -255,7 +468,23 @@ impl Prover {
     fn convert_target_proof<C: TargetCircuit>(
         &mut self,
         proof: &TargetCircuitProof,
-    ) -> anyhow::Result<ProvedCircuit> {
+    ) -> Result<ProvedCircuit> {
+        if proof.metadata.multiopen != MultiopenScheme::Gwc {
+            return Err(Error::Unsupported(format!(
+                "cannot aggregate {} proof of {} circuit",
+                proof.metadata.multiopen, proof.name
+            )));
+        }
+        let transcript = proof
+            .metadata
+            .transcript
+            .unwrap_or(TranscriptKind::Poseidon);
+        if transcript != TranscriptKind::Poseidon {
+            return Err(Error::Unsupported(format!(
+                "cannot aggregate {} transcript proof of {} circuit",
+                transcript, proof.name
+            )));
+        }
         let instances: Vec<Vec<Vec<u8>>> = serde_json::from_reader(&proof.instance[..])?;
         let instances = deserialize_fr_matrix(instances);
         //debug_assert!(instances.is_empty(), "instance not supported yet");
@@ -267,17 +496,16 @@ impl Prover {
                     VerifyingKey::<G1Affine>::read::<_, C::Inner>(
                         &mut Cursor::new(&proof.vk),
                         SerdeFormat::Processed,
-                    )
-                    .unwrap()
+                    )?
                 } else {
-                    keygen_vk(&self.params, &C::empty()).unwrap()
+                    keygen_vk(&self.params, &C::empty()).map_err(Error::keygen(C::name()))?
                 }
             }
         };
-        if *OPT_MEM {
-            Self::tick(&format!("before release pk of {}", C::name()));
+        if self.release_plan(self.pending_agg_pk_bytes()).target_pk {
+            self.tick(&format!("before release pk of {}", C::name()));
             self.target_circuit_pks.remove(&C::name());
-            Self::tick(&format!("after release pk of {}", &C::name()));
+            self.tick(&format!("after release pk of {}", &C::name()));
         }
 
         Ok(ProvedCircuit {
@@ -290,38 +518,40 @@ impl Prover {
         })
     }
 
-    pub fn create_solidity_verifier(&self, proof: &AggCircuitProof) -> String {
-        fn from_0_to_n<const N: usize>() -> [usize; N] {
-            core::array::from_fn(|i| i)
-        }
-        // NOTE: If any changes are made to circuit aggregation, names should be reflected, too.
-        let names = [SuperCircuit::name()];
-        MultiCircuitSolidityGenerate {
-            target_circuits_params: from_0_to_n::<1>().map(|circuit_index| SolidityGenerate {
-                target_circuit_params: self.params.clone(),
-                target_circuit_vk: self
-                    .target_circuit_pks
-                    .get(&names[circuit_index])
-                    .unwrap()
-                    .get_vk()
-                    .clone(),
-                nproofs: 1,
-            }),
-
-            verify_vk: self.agg_pk.as_ref().expect("pk should be inited").get_vk(),
-            verify_params: self.agg_params.as_ref().unwrap(),
-            verify_circuit_instance: load_instances(&proof.instance),
-            proof: proof.proof.clone(),
-            verify_public_inputs_size: 4, // not used now
+    pub fn create_solidity_verifier(&self, proof: &AggCircuitProof) -> Result<String> {
+        if proof.metadata.multiopen != MultiopenScheme::Gwc {
+            return Err(Error::Unsupported(format!(
+                "solidity verifier of {} agg proof",
+                proof.metadata.multiopen
+            )));
         }
-        .call("".into())
+        // See comments in `generate_verifier()`.
+        let target_vk = self
+            .target_circuit_pks
+            .get(&SuperCircuit::name())
+            .ok_or_else(|| Error::VkNotFound(SuperCircuit::name()))?
+            .get_vk()
+            .clone();
+        let agg_pk = self
+            .agg_pk
+            .as_ref()
+            .ok_or_else(|| Error::VkNotFound(AGG_VK_NAME.to_string()))?;
+        let agg_params = self.agg_params.as_ref().ok_or(Error::AggParamsReleased)?;
+        Ok(generate_verifier(
+            self.params.clone(),
+            target_vk,
+            agg_params,
+            agg_pk.get_vk(),
+            load_instances(&proof.instance),
+            proof.proof.clone(),
+        ))
     }
 
     pub fn create_agg_circuit_proof(
         &mut self,
         block_trace: &BlockTrace,
         create_verifier_sol: bool,
-    ) -> anyhow::Result<AggCircuitProof> {
+    ) -> Result<AggCircuitProof> {
         self.create_agg_circuit_proof_batch(&[block_trace.clone()], create_verifier_sol)
     }
 
@@ -329,10 +559,13 @@ impl Prover {
         &mut self,
         block_traces: &[BlockTrace],
         create_verifier_sol: bool,
-    ) -> anyhow::Result<AggCircuitProof> {
-        // See comments in `create_solidity_verifier()`.
-        let circuit_results: Vec<ProvedCircuit> =
-            vec![self.prove_circuit::<SuperCircuit>(block_traces)?];
+    ) -> Result<AggCircuitProof> {
+        self.check_agg_ready()?;
+        // See comments in `generate_verifier()`.
+        let circuit_results: Vec<ProvedCircuit> = vec![crate::with_super_circuit!(
+            *CIRCUIT_PROFILE,
+            C => self.prove_circuit::<C>(block_traces)?
+        )];
         self.create_agg_circuit_proof_impl(circuit_results, create_verifier_sol)
     }
 
@@ -340,7 +573,8 @@ impl Prover {
         &mut self,
         circuit_results: Vec<ProvedCircuit>,
         create_verifier_sol: bool,
-    ) -> anyhow::Result<AggCircuitProof> {
+    ) -> Result<AggCircuitProof> {
+        self.check_agg_ready()?;
         ///////////////////////////// build verifier circuit from block result ///////////////////
         let target_circuits = [0];
         let verifier_params = self.params.verifier_params();
@@ -379,16 +613,34 @@ impl Prover {
             final_pair_to_instances::<_, Bn256>(&verify_circuit_final_pair);
 
         if self.agg_pk.is_none() {
+            if self.release_plan(self.pending_agg_pk_bytes()).target_pk
+                && !self.target_circuit_pks.is_empty()
+            {
+                self.tick("before release target pks");
+                self.target_circuit_pks.clear();
+                self.tick("after release target pks");
+            }
+            if let Some(budget) = self.memory_budget {
+                let needed = self.pending_agg_pk_bytes();
+                let rss = current_rss().unwrap_or_default();
+                if rss.saturating_add(needed) > budget {
+                    return Err(Error::MemoryBudgetExceeded {
+                        needed: rss + needed,
+                        budget,
+                    });
+                }
+            }
+            let _phase = PhaseGuard::new(&self.observer, ProverPhase::AggKeygen, "agg");
             log::info!("generate agg pk: begin");
             let verify_circuit_vk = keygen_vk(self.agg_params.as_ref().unwrap(), &verify_circuit)
-                .expect("keygen_vk should not fail");
+                .map_err(Error::keygen("agg"))?;
             log::info!("generate agg pk: vk done");
             let verify_circuit_pk = keygen_pk(
                 self.agg_params.as_ref().unwrap(),
                 verify_circuit_vk,
                 &verify_circuit,
             )
-            .expect("keygen_pk should not fail");
+            .map_err(Error::keygen("agg"))?;
             self.agg_pk = Some(verify_circuit_pk);
             log::info!("init_agg_pk: done");
         } else {
@@ -402,25 +654,41 @@ impl Prover {
         let mut transcript = TachyonSha256Write::init(vec![]);
 
         if *MOCK_PROVE {
+            let _phase = PhaseGuard::new(&self.observer, ProverPhase::MockProve, "agg");
             log::info!("mock prove agg circuit");
             let prover = MockProver::<Fr>::run(
                 *AGG_DEGREE as u32,
                 &verify_circuit,
                 vec![verify_circuit_instances.clone()],
-            )?;
+            )
+            .map_err(|e| Error::MockProve {
+                circuit: "agg".to_string(),
+                failures: e.to_string(),
+            })?;
             if let Err(errs) = prover.verify_par() {
                 log::error!("err num: {}", errs.len());
                 for err in &errs {
                     log::error!("{}", err);
                 }
-                bail!("{:#?}", errs);
+                return Err(Error::MockProve {
+                    circuit: "agg".to_string(),
+                    failures: format!("{errs:#?}"),
+                });
             }
             log::info!("mock prove agg circuit done");
         }
 
+        let agg_phase = PhaseGuard::new(&self.observer, ProverPhase::AggProve, "agg");
         #[cfg(feature = "tachyon")]
         let proof = {
             log::info!("create agg proof by tachyon prover");
+            if self.agg_multiopen != MultiopenScheme::Gwc {
+                return Err(Error::Unsupported(format!(
+                    "{} agg proof with tachyon prover",
+                    self.agg_multiopen
+                )));
+            }
+            let rng = self.tachyon_rng();
             let mut tachyon_agg_pk = {
                 let mut pk_bytes: Vec<u8> = vec![];
                 self.agg_pk
@@ -437,7 +705,7 @@ impl Prover {
                 agg_params_ref.write(&mut params_bytes).unwrap();
                 let k = agg_params_ref.k();
                 if !create_verifier_sol {
-                    self.agg_params = None;
+                    self.release_agg_params();
                 }
                 TachyonGWCProver::<KZGCommitmentScheme<Bn256>>::from_params(
                     TranscriptType::Sha256 as u8,
@@ -451,10 +719,10 @@ impl Prover {
                 &mut tachyon_agg_pk,
                 &[verify_circuit],
                 instances_slice,
-                self.rng.clone(),
+                rng,
                 &mut transcript,
             )
-            .expect("proof generation should not fail");
+            .map_err(Error::proof("agg"))?;
             let mut proof = transcript.finalize();
             let proof_last = prover.get_proof();
             proof.extend_from_slice(&proof_last);
@@ -462,17 +730,24 @@ impl Prover {
         };
         #[cfg(not(feature = "tachyon"))]
         let proof = {
-            log::info!("create agg proof");
-            create_proof::<KZGCommitmentScheme<_>, ProverGWC<_>, _, _, _, _>(
-                &self.agg_params.as_ref().unwrap(),
+            log::info!("create agg proof with {} multiopen", self.agg_multiopen);
+            create_kzg_proof(
+                self.agg_params.as_ref().unwrap(),
                 self.agg_pk.as_ref().unwrap(),
-                &[verify_circuit],
+                verify_circuit,
                 instances_slice,
-                self.rng.clone(),
+                self.agg_multiopen,
+                &mut self.rng,
                 &mut transcript,
-            )?;
+            )
+            .map_err(Error::proof("agg"))?;
             transcript.finalize()
         };
+        drop(agg_phase);
+        #[cfg(not(feature = "tachyon"))]
+        if !create_verifier_sol && self.release_plan(0).agg_params {
+            self.release_agg_params();
+        }
 
         log::info!(
             "create agg proof done, block proved {}/{}",
@@ -490,18 +765,23 @@ impl Prover {
             final_pair,
             vk: vk_bytes,
             block_count: circuit_results[0].proved_block_count,
+            metadata: self.proof_metadata(self.agg_multiopen, TranscriptKind::Sha256),
         })
     }
 
     pub fn mock_prove_target_circuit<C: TargetCircuit>(
         block_trace: &BlockTrace,
-    ) -> anyhow::Result<()> {
-        Self::mock_prove_target_circuit_batch::<C>(&[block_trace.clone()])
+        observer: &Arc<dyn ProverObserver>,
+    ) -> Result<()> {
+        Self::mock_prove_target_circuit_batch::<C>(&[block_trace.clone()], observer)
     }
 
+    /// Mock proves `block_traces` of the configured [`CHAIN_ID`], reporting its phases to
+    /// `observer` like a real proof reports them to [`Prover::observer`].
     pub fn mock_prove_target_circuit_batch<C: TargetCircuit>(
         block_traces: &[BlockTrace],
-    ) -> anyhow::Result<()> {
+        observer: &Arc<dyn ProverObserver>,
+    ) -> Result<()> {
         log::info!(
             "start mock prove {} circuit, batch range {:?} to {:?}",
             C::name(),
@@ -511,22 +791,18 @@ impl Prover {
         log::info!("rows needed {:?}", C::estimate_rows(block_traces));
         let original_block_len = block_traces.len();
         let mut block_traces = block_traces.to_vec();
-        check_batch_capacity(&mut block_traces)?;
-        let witness_block = block_traces_to_witness_block(&block_traces)?;
+        let builder = {
+            let _phase = PhaseGuard::new(observer, ProverPhase::CheckCapacity, &C::name());
+            check_batch_capacity(&mut block_traces, *CHAIN_ID, &**observer)?
+        };
+        let build_phase = PhaseGuard::new(observer, ProverPhase::BuildWitness, &C::name());
+        let witness_block = builder.finalize()?;
         log::info!(
             "mock proving batch of len {}, batch metric {:?}",
             original_block_len,
             metric_of_witness_block(&witness_block)
         );
-        let (circuit, instance) = C::from_witness_block(&witness_block)?;
-        let prover = MockProver::<Fr>::run(*DEGREE as u32, &circuit, instance)?;
-        if let Err(errs) = prover.verify_par() {
-            log::error!("err num: {}", errs.len());
-            for err in &errs {
-                log::error!("{}", err);
-            }
-            bail!("{:?}", errs);
-        }
+        Self::mock_prove_witness_block::<C>(&witness_block, build_phase, observer)?;
         log::info!(
             "mock prove {} done. block proved {}/{}, batch metric: {:?}",
             C::name(),
@@ -537,29 +813,119 @@ impl Prover {
         Ok(())
     }
 
+    /// Mock proves `witness_block`, closing `build_phase` once the circuit is built.
+    fn mock_prove_witness_block<C: TargetCircuit>(
+        witness_block: &Block<Fr>,
+        build_phase: PhaseGuard,
+        observer: &Arc<dyn ProverObserver>,
+    ) -> Result<()> {
+        let (circuit, instance) = C::from_witness_block(witness_block)?;
+        drop(build_phase);
+        let _phase = PhaseGuard::new(observer, ProverPhase::MockProve, &C::name());
+        let prover = MockProver::<Fr>::run(*DEGREE as u32, &circuit, instance).map_err(|e| {
+            Error::MockProve {
+                circuit: C::name(),
+                failures: e.to_string(),
+            }
+        })?;
+        if let Err(errs) = prover.verify_par() {
+            log::error!("err num: {}", errs.len());
+            for err in &errs {
+                log::error!("{}", err);
+            }
+            return Err(Error::MockProve {
+                circuit: C::name(),
+                failures: format!("{errs:?}"),
+            });
+        }
+        Ok(())
+    }
+
+    /// Mock proves the batch of a bundle saved with `DUMP_TRACE_BUNDLE`, for the chain id it was
+    /// built for and without checking its capacity again.
+    pub fn mock_prove_trace_bundle<C: TargetCircuit>(
+        path: impl AsRef<Path>,
+        observer: &Arc<dyn ProverObserver>,
+    ) -> Result<()> {
+        let bundle = TraceBundle::load(path)?;
+        let build_phase = PhaseGuard::new(observer, ProverPhase::BuildWitness, &C::name());
+        let witness_block = bundle.witness_block()?;
+        Self::mock_prove_witness_block::<C>(&witness_block, build_phase, observer)?;
+        log::info!("mock prove {} of trace bundle done", C::name());
+        Ok(())
+    }
+
+    /// Proves the batch of a bundle saved with `DUMP_TRACE_BUNDLE`, for the chain id it was built
+    /// for and without checking its capacity again.
+    pub fn create_target_circuit_proof_from_trace_bundle<C: TargetCircuit>(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<TargetCircuitProof> {
+        let bundle = TraceBundle::load(path)?;
+        let build_phase = PhaseGuard::new(&self.observer, ProverPhase::BuildWitness, &C::name());
+        let witness_block = bundle.witness_block()?;
+        self.create_target_circuit_proof_of_witness::<C>(
+            &bundle.block_traces,
+            bundle.block_traces.len(),
+            witness_block,
+            build_phase,
+        )
+    }
+
     pub fn create_target_circuit_proof<C: TargetCircuit>(
         &mut self,
         block_trace: &BlockTrace,
-    ) -> anyhow::Result<TargetCircuitProof, Error> {
+    ) -> Result<TargetCircuitProof> {
         self.create_target_circuit_proof_batch::<C>(&[block_trace.clone()])
     }
 
     pub fn create_target_circuit_proof_batch<C: TargetCircuit>(
         &mut self,
         block_traces: &[BlockTrace],
-    ) -> anyhow::Result<TargetCircuitProof, Error> {
+    ) -> Result<TargetCircuitProof> {
         let original_block_count = block_traces.len();
         let mut block_traces = block_traces.to_vec();
-        check_batch_capacity(&mut block_traces)?;
-        let witness_block = block_traces_to_witness_block(&block_traces)?;
+        let builder = {
+            let _phase = PhaseGuard::new(&self.observer, ProverPhase::CheckCapacity, &C::name());
+            check_batch_capacity(&mut block_traces, self.chain_id, &*self.observer)?
+        };
+        if *DUMP_TRACE_BUNDLE && !self.debug_dir.is_empty() {
+            let path = format!("{}/{}_traces.bundle", self.debug_dir, C::name());
+            TraceBundle::new(block_traces.clone(), builder.chain_id()).save(&path)?;
+            log::info!("traces of {} circuit saved to {}", C::name(), path);
+        }
+        let build_phase = PhaseGuard::new(&self.observer, ProverPhase::BuildWitness, &C::name());
+        let witness_block = builder.finalize()?;
+        self.create_target_circuit_proof_of_witness::<C>(
+            &block_traces,
+            original_block_count,
+            witness_block,
+            build_phase,
+        )
+    }
+
+    /// Proves `witness_block` of `block_traces`, which were kept of `original_block_count`
+    /// blocks, closing `build_phase` once the circuit is built.
+    fn create_target_circuit_proof_of_witness<C: TargetCircuit>(
+        &mut self,
+        block_traces: &[BlockTrace],
+        original_block_count: usize,
+        witness_block: Block<Fr>,
+        build_phase: PhaseGuard,
+    ) -> Result<TargetCircuitProof> {
         log::info!(
             "proving batch of len {}, batch metric {:?}",
             original_block_count,
             metric_of_witness_block(&witness_block)
         );
         let (circuit, instance) = C::from_witness_block(&witness_block)?;
-        #[cfg(not(feature = "tachyon"))]
-        let mut transcript = PoseidonWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+        drop(build_phase);
+        let proved_block_count = witness_block.context.ctxs.len();
+        if self.release_plan(0).witness {
+            self.tick("before release witness block");
+            drop(witness_block);
+            self.tick("after release witness block");
+        }
         #[cfg(feature = "tachyon")]
         let mut transcript = TachyonPoseidonWrite::init(vec![]);
 
@@ -575,24 +941,50 @@ impl Prover {
             block_traces.len()
         );
         if *MOCK_PROVE {
+            let _phase = PhaseGuard::new(&self.observer, ProverPhase::MockProve, &C::name());
             log::info!("mock prove {} start", C::name());
-            let prover = MockProver::<Fr>::run(*DEGREE as u32, &circuit, instance.clone())?;
+            let prover = MockProver::<Fr>::run(*DEGREE as u32, &circuit, instance.clone())
+                .map_err(|e| Error::MockProve {
+                    circuit: C::name(),
+                    failures: e.to_string(),
+                })?;
             if let Err(errs) = prover.verify_par() {
                 log::error!("err num: {}", errs.len());
                 for err in &errs {
                     log::error!("{}", err);
                 }
-                bail!("{:#?}", errs);
+                return Err(Error::MockProve {
+                    circuit: C::name(),
+                    failures: format!("{errs:#?}"),
+                });
             }
             log::info!("mock prove {} done", C::name());
         }
 
         if !self.target_circuit_pks.contains_key(&C::name()) {
             //self.init_pk::<C>(&circuit);
-            self.init_pk::<C>(&C::empty());
+            self.init_pk::<C>(&C::empty())?;
         }
+        #[cfg(feature = "tachyon")]
+        if self.target_multiopen != MultiopenScheme::Gwc {
+            return Err(Error::Unsupported(format!(
+                "{} target proof with tachyon prover",
+                self.target_multiopen
+            )));
+        }
+        #[cfg(feature = "tachyon")]
+        if self.target_transcript != TranscriptKind::Poseidon {
+            return Err(Error::Unsupported(format!(
+                "{} transcript target proof with tachyon prover",
+                self.target_transcript
+            )));
+        }
+        #[cfg(feature = "tachyon")]
+        let rng = self.tachyon_rng();
+        let metadata = self.proof_metadata(self.target_multiopen, self.target_transcript);
         let pk = &self.target_circuit_pks[&C::name()];
 
+        let prove_phase = PhaseGuard::new(&self.observer, ProverPhase::TargetProve, &C::name());
         #[cfg(feature = "tachyon")]
         let proof = {
             let mut tachyon_pk = {
@@ -617,27 +1009,53 @@ impl Prover {
                 &mut tachyon_pk,
                 &[circuit],
                 public_inputs,
-                self.rng.clone(),
+                rng,
                 &mut transcript,
             )
-            .expect("proof generation should not fail");
+            .map_err(Error::proof(C::name()))?;
             let mut proof = transcript.finalize();
             let proof_last = prover.get_proof();
             proof.extend_from_slice(&proof_last);
             proof
         };
         #[cfg(not(feature = "tachyon"))]
-        let proof = {
-            create_proof::<KZGCommitmentScheme<_>, ProverGWC<_>, _, _, _, _>(
-                &self.params,
-                pk,
-                &[circuit],
-                public_inputs,
-                self.rng.clone(),
-                &mut transcript,
-            )?;
-            transcript.finalize()
+        let proof = match self.target_transcript {
+            TranscriptKind::Poseidon => {
+                let mut transcript = PoseidonWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+                create_kzg_proof(
+                    &self.params,
+                    pk,
+                    circuit,
+                    public_inputs,
+                    self.target_multiopen,
+                    &mut self.rng,
+                    &mut transcript,
+                )
+                .map_err(Error::proof(C::name()))?;
+                transcript.finalize()
+            }
+            TranscriptKind::Keccak => {
+                let mut transcript =
+                    ShaWrite::<_, G1Affine, Challenge255<_>, Keccak256>::init(vec![]);
+                create_kzg_proof(
+                    &self.params,
+                    pk,
+                    circuit,
+                    public_inputs,
+                    self.target_multiopen,
+                    &mut self.rng,
+                    &mut transcript,
+                )
+                .map_err(Error::proof(C::name()))?;
+                transcript.finalize()
+            }
+            TranscriptKind::Sha256 => {
+                return Err(Error::Unsupported(
+                    "sha256 transcript for target proofs".to_string(),
+                ))
+            }
         };
+        drop(prove_phase);
 
         info!(
             "Create {} proof of block {} ... block {} Successfully!",
@@ -659,7 +1077,8 @@ impl Prover {
             instance: instance_bytes,
             vk: serialize_vk(pk.get_vk()),
             original_block_count,
-            proved_block_count: witness_block.context.ctxs.len(),
+            proved_block_count,
+            metadata,
         };
         if !self.debug_dir.is_empty() {
             // write vk
@@ -673,7 +1092,48 @@ impl Prover {
             let output_file = format!("{}/{}_proof.json", self.debug_dir, name);
             let mut fd = std::fs::File::create(output_file).unwrap();
             serde_json::to_writer_pretty(&mut fd, &target_proof).unwrap();
+
+            if self.target_transcript == TranscriptKind::Keccak {
+                let calldata = target_proof.evm_calldata()?;
+                let output_file = format!("{}/{}_calldata.data", self.debug_dir, name);
+                std::fs::write(output_file, calldata)?;
+            }
         }
         Ok(target_proof)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Prover;
+    use crate::error::Error;
+    use crate::utils::load_or_create_params;
+    use halo2_proofs::poly::commitment::Params;
+
+    const K: usize = 4;
+
+    #[test]
+    fn test_reload_released_agg_params() {
+        let dir = std::env::temp_dir().join(format!("agg_params_{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let params = load_or_create_params(dir, K).unwrap();
+
+        let mut prover = Prover::new(params.clone(), Some(params.clone()));
+        prover.agg_params_dir = Some(dir.to_string());
+        prover.memory_budget = Some(u64::MAX);
+        for _ in 0..2 {
+            prover.release_agg_params();
+            assert!(prover.agg_params.is_none());
+            prover.check_agg_ready().unwrap();
+            assert_eq!(prover.agg_params.as_ref().unwrap().k(), K as u32);
+        }
+
+        let mut prover = Prover::new(params.clone(), Some(params));
+        prover.release_agg_params();
+        assert!(matches!(
+            prover.check_agg_ready(),
+            Err(Error::AggParamsReleased)
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use crate::utils::read_env_var;
+use once_cell::sync::Lazy;
+
+/// Memory budget of the prover process in bytes, read from `MEMORY_BUDGET_GB`.
+/// 0 means no budget.
+pub static MEMORY_BUDGET: Lazy<Option<u64>> = Lazy::new(|| {
+    let gb: u64 = read_env_var("MEMORY_BUDGET_GB", 0);
+    (gb != 0).then_some(gb << 30)
+});
+
+/// Estimated size of the agg pk per row of the aggregation circuit, read from
+/// `AGG_PK_BYTES_PER_ROW`.
+pub static AGG_PK_BYTES_PER_ROW: Lazy<u64> =
+    Lazy::new(|| read_env_var("AGG_PK_BYTES_PER_ROW", 640));
+
+/// Resident set size of this process in bytes.
+pub fn current_rss() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = procfs::process::Process::myself().ok()?.status().ok()?;
+        status.vmrss.map(|kb| kb * 1024)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Peak resident set size of this process in bytes since the last [`reset_peak_rss`].
+pub fn peak_rss() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = procfs::process::Process::myself().ok()?.status().ok()?;
+        status.vmhwm.map(|kb| kb * 1024)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Resets the peak resident set size to the current one. See `clear_refs` in proc(5).
+pub fn reset_peak_rss() {
+    #[cfg(target_os = "linux")]
+    if let Err(e) = std::fs::write("/proc/self/clear_refs", "5") {
+        log::debug!("failed to reset peak rss: {}", e);
+    }
+}
+
+pub(crate) fn estimate_agg_pk_bytes(degree: usize) -> u64 {
+    (1u64 << degree) * *AGG_PK_BYTES_PER_ROW
+}
+
+/// What the prover releases between phases to stay within its memory budget.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ReleasePlan {
+    pub target_pk: bool,
+    pub agg_params: bool,
+    pub witness: bool,
+}
+
+impl ReleasePlan {
+    /// Plans the releases of a prover that holds `rss` bytes and still has to allocate
+    /// `pending` bytes, e.g. for the agg pk.
+    pub fn new(budget: Option<u64>, rss: u64, pending: u64, opt_mem: bool) -> Self {
+        match budget {
+            None => Self {
+                target_pk: opt_mem,
+                ..Default::default()
+            },
+            Some(budget) => {
+                let over = rss.saturating_add(pending) > budget;
+                Self {
+                    target_pk: opt_mem || over,
+                    agg_params: over,
+                    witness: true,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReleasePlan;
+
+    const GB: u64 = 1 << 30;
+
+    #[test]
+    fn test_release_plan() {
+        assert_eq!(
+            ReleasePlan::new(None, 100 * GB, 100 * GB, false),
+            ReleasePlan::default()
+        );
+        assert!(ReleasePlan::new(None, 0, 0, true).target_pk);
+
+        let plan = ReleasePlan::new(Some(64 * GB), 20 * GB, 40 * GB, false);
+        assert_eq!(
+            plan,
+            ReleasePlan {
+                target_pk: false,
+                agg_params: false,
+                witness: true,
+            }
+        );
+
+        let plan = ReleasePlan::new(Some(64 * GB), 30 * GB, 40 * GB, false);
+        assert_eq!(
+            plan,
+            ReleasePlan {
+                target_pk: true,
+                agg_params: true,
+                witness: true,
+            }
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use serde_derive::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Source of the blinding randomness a proof was created with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RngMode {
+    /// Proofs serialized before the mode was recorded.
+    #[default]
+    Unknown,
+    /// Randomness drawn from the operating system. This is the production default.
+    Os,
+    /// Randomness source injected by the caller.
+    Injected,
+    /// Seeded from a fixed value. Every proof reuses the same blinding, so this is for
+    /// tests only and such proofs must not be treated as zero-knowledge.
+    Deterministic,
+}
+
+/// KZG multiopen argument a proof was created with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiopenScheme {
+    /// Gabizon-Williamson-Ciobotaru. The aggregation circuit and the generated Solidity
+    /// verifier only support this scheme.
+    #[default]
+    Gwc,
+    /// Boneh-Drake-Fisch-Gabizon, as in halo2's `shplonk` module. Gives smaller proofs.
+    Shplonk,
+}
+
+impl MultiopenScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MultiopenScheme::Gwc => "gwc",
+            MultiopenScheme::Shplonk => "shplonk",
+        }
+    }
+}
+
+impl std::fmt::Display for MultiopenScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MultiopenScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gwc" => Ok(MultiopenScheme::Gwc),
+            "shplonk" => Ok(MultiopenScheme::Shplonk),
+            _ => Err(format!("unknown multiopen scheme: {s}")),
+        }
+    }
+}
+
+/// Fiat-Shamir transcript a proof was created with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptKind {
+    /// Cheap to verify in-circuit. Used by target proofs that get aggregated.
+    Poseidon,
+    /// Used by aggregation proofs.
+    Sha256,
+    /// Cheap to verify on-chain. Target proofs with this transcript skip aggregation.
+    Keccak,
+}
+
+impl TranscriptKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TranscriptKind::Poseidon => "poseidon",
+            TranscriptKind::Sha256 => "sha256",
+            TranscriptKind::Keccak => "keccak",
+        }
+    }
+}
+
+impl std::fmt::Display for TranscriptKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TranscriptKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "poseidon" => Ok(TranscriptKind::Poseidon),
+            "sha256" => Ok(TranscriptKind::Sha256),
+            "keccak" => Ok(TranscriptKind::Keccak),
+            _ => Err(format!("unknown transcript: {s}")),
+        }
+    }
+}
+
+/// Describes how a proof was created.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofMetadata {
+    #[serde(default)]
+    pub rng_mode: RngMode,
+    /// Proofs serialized before the scheme was recorded were always created with GWC.
+    #[serde(default)]
+    pub multiopen: MultiopenScheme,
+    /// `None` for proofs serialized before the transcript was recorded, which used
+    /// Poseidon for target proofs and SHA-256 for aggregation proofs.
+    #[serde(default)]
+    pub transcript: Option<TranscriptKind>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_metadata_defaults_to_gwc() {
+        let metadata: ProofMetadata = serde_json::from_str("{}").unwrap();
+        assert_eq!(metadata.multiopen, MultiopenScheme::Gwc);
+        assert_eq!(metadata.rng_mode, RngMode::Unknown);
+        assert_eq!(metadata.transcript, None);
+
+        let metadata: ProofMetadata =
+            serde_json::from_str(r#"{"rng_mode":"os","multiopen":"shplonk"}"#).unwrap();
+        assert_eq!(metadata.multiopen, MultiopenScheme::Shplonk);
+        assert_eq!(
+            "SHPLONK".parse::<MultiopenScheme>(),
+            Ok(MultiopenScheme::Shplonk)
+        );
+    }
+}
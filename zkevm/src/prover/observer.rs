@@ -0,0 +1,187 @@
+use super::memory::{peak_rss, reset_peak_rss};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Phases of a proving run, reported to [`ProverObserver`] in the order they happen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProverPhase {
+    CheckCapacity,
+    BuildWitness,
+    MockProve,
+    TargetKeygen,
+    TargetProve,
+    AggKeygen,
+    AggProve,
+}
+
+impl ProverPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProverPhase::CheckCapacity => "check capacity",
+            ProverPhase::BuildWitness => "build witness",
+            ProverPhase::MockProve => "mock prove",
+            ProverPhase::TargetKeygen => "target keygen",
+            ProverPhase::TargetProve => "target prove",
+            ProverPhase::AggKeygen => "agg keygen",
+            ProverPhase::AggProve => "agg prove",
+        }
+    }
+}
+
+impl fmt::Display for ProverPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Hooks called by `Prover` while it works. Every method has an empty default, so an
+/// implementation only needs to override the events it cares about.
+pub trait ProverObserver: Send + Sync {
+    fn on_phase_start(&self, _phase: ProverPhase, _circuit: &str) {}
+    fn on_phase_end(&self, _phase: ProverPhase, _circuit: &str, _elapsed: Duration) {}
+    /// Accumulated row usage per sub circuit after adding the block `block_number`.
+    fn on_row_usage(&self, _block_number: Option<u64>, _rows: &[(&'static str, usize)]) {}
+    /// `check_batch_capacity` keeps the first `kept` blocks out of `total`.
+    fn on_truncate(&self, _kept: usize, _total: usize) {}
+    /// Resident set size of the prover process, sampled at the point described by `desc`.
+    fn on_memory_sample(&self, _desc: &str, _bytes: u64) {}
+    /// Peak resident set size of the prover process during `phase`.
+    fn on_peak_rss(&self, _phase: ProverPhase, _circuit: &str, _bytes: u64) {}
+}
+
+/// Default observer, writing every event to the log.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogObserver;
+
+impl ProverObserver for LogObserver {
+    fn on_phase_start(&self, phase: ProverPhase, circuit: &str) {
+        log::info!("{} of {} circuit: begin", phase, circuit);
+    }
+
+    fn on_phase_end(&self, phase: ProverPhase, circuit: &str, elapsed: Duration) {
+        log::info!(
+            "{} of {} circuit: done, elapsed {:?}",
+            phase,
+            circuit,
+            elapsed
+        );
+    }
+
+    fn on_row_usage(&self, block_number: Option<u64>, rows: &[(&'static str, usize)]) {
+        log::debug!(
+            "row usage after block {:?}: {:?}, {:?}",
+            block_number,
+            rows.iter().map(|(_, r)| *r).max().unwrap_or_default(),
+            rows
+        );
+    }
+
+    fn on_truncate(&self, kept: usize, total: usize) {
+        log::warn!("truncate blocks [{}..{})", kept, total);
+    }
+
+    fn on_memory_sample(&self, desc: &str, bytes: u64) {
+        log::debug!(
+            "memory usage when {}: {:?}GB",
+            desc,
+            bytes / 1024 / 1024 / 1024
+        );
+    }
+
+    fn on_peak_rss(&self, phase: ProverPhase, circuit: &str, bytes: u64) {
+        log::info!(
+            "peak memory of {} of {} circuit: {:.2}GB",
+            phase,
+            circuit,
+            bytes as f64 / (1u64 << 30) as f64
+        );
+    }
+}
+
+/// Reports the start of `phase` on creation and its end and peak memory when dropped, so
+/// early returns through `?` still close the phase.
+pub(crate) struct PhaseGuard {
+    observer: Arc<dyn ProverObserver>,
+    phase: ProverPhase,
+    circuit: String,
+    start: Instant,
+}
+
+impl PhaseGuard {
+    pub(crate) fn new(
+        observer: &Arc<dyn ProverObserver>,
+        phase: ProverPhase,
+        circuit: &str,
+    ) -> Self {
+        observer.on_phase_start(phase, circuit);
+        reset_peak_rss();
+        Self {
+            observer: observer.clone(),
+            phase,
+            circuit: circuit.to_string(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        self.observer
+            .on_phase_end(self.phase, &self.circuit, self.start.elapsed());
+        if let Some(peak) = peak_rss() {
+            self.observer.on_peak_rss(self.phase, &self.circuit, peak);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PhaseGuard, ProverObserver, ProverPhase};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl ProverObserver for RecordingObserver {
+        fn on_phase_start(&self, phase: ProverPhase, circuit: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("start {phase} {circuit}"));
+        }
+
+        fn on_phase_end(&self, phase: ProverPhase, circuit: &str, _elapsed: Duration) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("end {phase} {circuit}"));
+        }
+    }
+
+    #[test]
+    fn test_phase_guard_order() {
+        let recorder = Arc::new(RecordingObserver::default());
+        let observer: Arc<dyn ProverObserver> = recorder.clone();
+        {
+            let _prove = PhaseGuard::new(&observer, ProverPhase::TargetProve, "super");
+            let _keygen = PhaseGuard::new(&observer, ProverPhase::TargetKeygen, "super");
+        }
+        drop(PhaseGuard::new(&observer, ProverPhase::AggProve, "agg"));
+
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec![
+                "start target prove super",
+                "start target keygen super",
+                "end target keygen super",
+                "end target prove super",
+                "start agg prove agg",
+                "end agg prove agg",
+            ]
+        );
+    }
+}
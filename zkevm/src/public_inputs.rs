@@ -0,0 +1,293 @@
+use crate::circuit::{
+    block_traces_to_witness_block_with_chain_id, ChainTip, TargetCircuit, CHAIN_ID, CIRCUIT_PROFILE,
+};
+use crate::error::{Error, Result};
+use crate::io::deserialize_fr_matrix;
+use crate::prover::{AggCircuitProof, TargetCircuitProof};
+use eth_types::H256;
+use halo2_proofs::arithmetic::{CurveAffine, Field};
+use halo2_proofs::halo2curves::bn256::{Bn256, Fq, Fr, G1Affine, G2Prepared, G1};
+use halo2_proofs::halo2curves::group::{Curve, Group};
+use halo2_proofs::halo2curves::pairing::{MillerLoopResult, MultiMillerLoop};
+use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+use halo2_snark_aggregator_circuit::verify_circuit::final_pair_to_instances;
+use rand::rngs::OsRng;
+use types::eth::BlockTrace;
+
+/// Public inputs of an aggregation proof, decoded from `AggCircuitProof::final_pair`
+/// and `AggCircuitProof::instance`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofPublicInputs {
+    /// Left side of the accumulated pairing check, `e(lhs, [s]_2) == e(rhs, [1]_2)`.
+    pub accumulator_lhs: G1Affine,
+    /// Right side of the accumulated pairing check.
+    pub accumulator_rhs: G1Affine,
+    /// Limbs of the accumulator coordinates, as exposed in the agg circuit instance.
+    pub accumulator_limbs: Vec<Fr>,
+    /// Instances of the aggregated target circuit, i.e. the public inputs of the PI
+    /// circuit of the SuperCircuit.
+    pub target_instances: Vec<Fr>,
+}
+
+/// Block-level values a batch of traces exposes. Returned by
+/// [`ProofPublicInputs::check_block_traces`] and [`BlockPublicInputs::from_target_proof`]
+/// once the proof is known to attest to them.
+///
+/// There is no withdrawal root. The target instances are only the keccak hash of the block
+/// public data, so no value can be decoded from a proof, only confirmed by recomputing the
+/// hash from the traces. The traces do not carry the storage root of the
+/// `L2ToL1MessagePasser`, so it can neither be recomputed nor returned here. The
+/// `withdrawalsRoot` of the header is the EIP-4895 withdrawals root, which is a different
+/// value, always the empty trie root on L2.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockPublicInputs {
+    pub chain_id: u64,
+    pub first_block_number: u64,
+    pub last_block_number: u64,
+    pub prev_state_root: H256,
+    pub state_root: H256,
+    pub block_hash: H256,
+    /// Values of every block of the batch, in order.
+    pub blocks: Vec<InnerBlockPublicInputs>,
+}
+
+/// Values of one block of a batch. They are part of the block public data the PI circuit
+/// hashes, so a matching instance attests to every block, not only to the ends of the batch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InnerBlockPublicInputs {
+    pub number: u64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub timestamp: u64,
+    pub num_txs: usize,
+    pub prev_state_root: H256,
+    pub state_root: H256,
+}
+
+impl InnerBlockPublicInputs {
+    fn from_block_trace(block_trace: &BlockTrace) -> Self {
+        Self {
+            number: block_trace.header.number.unwrap_or_default().as_u64(),
+            hash: block_trace.header.hash.unwrap_or_default(),
+            parent_hash: block_trace.header.parent_hash,
+            timestamp: block_trace.header.timestamp.as_u64(),
+            num_txs: block_trace.transactions.len(),
+            prev_state_root: block_trace.storage_trace.root_before,
+            state_root: block_trace.storage_trace.root_after,
+        }
+    }
+}
+
+impl BlockPublicInputs {
+    /// Checks that the instances of the SuperCircuit `proof` are those of `block_traces`,
+    /// with their witness built for `chain_id`, and returns the block-level values of the
+    /// traces.
+    pub fn from_target_proof(
+        proof: &TargetCircuitProof,
+        block_traces: &[BlockTrace],
+        chain_id: u64,
+    ) -> Result<Self> {
+        let instances: Vec<Vec<Vec<u8>>> = serde_json::from_slice(&proof.instance)?;
+        let instances = deserialize_fr_matrix(instances);
+        check_instances(&instances.concat(), block_traces, chain_id)?;
+        Self::from_block_traces(block_traces)
+    }
+
+    /// Values of `block_traces`, which must only be returned after checking the instances
+    /// of a proof against them.
+    fn from_block_traces(block_traces: &[BlockTrace]) -> Result<Self> {
+        let (first, last) = match (block_traces.first(), block_traces.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(Error::witness("empty block traces")),
+        };
+        // The roots of the batch only cover the blocks in between if they are chained.
+        for pair in block_traces.windows(2) {
+            ChainTip::of(&pair[0]).check_next(&pair[1])?;
+        }
+        Ok(Self {
+            chain_id: first.chain_id.as_u64(),
+            first_block_number: first.header.number.unwrap_or_default().as_u64(),
+            last_block_number: last.header.number.unwrap_or_default().as_u64(),
+            prev_state_root: first.storage_trace.root_before,
+            state_root: last.storage_trace.root_after,
+            block_hash: last.header.hash.unwrap_or_default(),
+            blocks: block_traces
+                .iter()
+                .map(InnerBlockPublicInputs::from_block_trace)
+                .collect(),
+        })
+    }
+}
+
+/// Checks `instances` against the instances of the SuperCircuit of [`CIRCUIT_PROFILE`]
+/// built from `block_traces` for `chain_id`.
+fn check_instances(instances: &[Fr], block_traces: &[BlockTrace], chain_id: u64) -> Result<()> {
+    let witness_block = block_traces_to_witness_block_with_chain_id(block_traces, chain_id)?;
+    let (_, expected) = crate::with_super_circuit!(
+        *CIRCUIT_PROFILE,
+        C => C::from_witness_block(&witness_block)?
+    );
+    if expected.concat() != instances {
+        return Err(Error::InvalidProof(
+            "public inputs do not match block traces".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn decode_fq(bytes: &[u8]) -> Option<Fq> {
+    Option::from(Fq::from_bytes(&bytes.try_into().ok()?))
+}
+
+fn decode_fr(bytes: &[u8]) -> Option<Fr> {
+    Option::from(Fr::from_bytes(&bytes.try_into().ok()?))
+}
+
+fn decode_g1(bytes: &[u8]) -> Option<G1Affine> {
+    let x = decode_fq(&bytes[..32])?;
+    let y = decode_fq(&bytes[32..64])?;
+    Option::from(G1Affine::from_xy(x, y))
+}
+
+impl ProofPublicInputs {
+    pub fn from_agg_proof(proof: &AggCircuitProof) -> Result<Self> {
+        Self::decode(&proof.final_pair, &proof.instance)
+    }
+
+    /// Decodes `final_pair` as written by `serialize_verify_circuit_final_pair` and checks
+    /// that `instance` holds exactly the agg circuit instances derived from it.
+    pub fn decode(final_pair: &[u8], instance: &[u8]) -> Result<Self> {
+        if final_pair.len() < 128 || final_pair.len() % 32 != 0 {
+            return Err(Error::InvalidProof(format!(
+                "invalid final pair len {}",
+                final_pair.len()
+            )));
+        }
+        let invalid_point = || Error::InvalidProof("final pair point not on curve".to_string());
+        let accumulator_lhs = decode_g1(&final_pair[..64]).ok_or_else(invalid_point)?;
+        let accumulator_rhs = decode_g1(&final_pair[64..128]).ok_or_else(invalid_point)?;
+        let target_instances = final_pair[128..]
+            .chunks(32)
+            .map(decode_fr)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| Error::InvalidProof("final pair scalar out of range".to_string()))?;
+
+        let accumulator_limbs =
+            final_pair_to_instances::<_, Bn256>(&(accumulator_lhs, accumulator_rhs, vec![]));
+        let expected = final_pair_to_instances::<_, Bn256>(&(
+            accumulator_lhs,
+            accumulator_rhs,
+            target_instances.clone(),
+        ));
+        let actual: Vec<Vec<Vec<Vec<u8>>>> = serde_json::from_slice(instance)?;
+        let actual = actual
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|bytes| decode_fr(bytes))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| Error::InvalidProof("agg instance out of range".to_string()))?;
+        if actual != expected {
+            return Err(Error::InvalidProof(
+                "agg instance does not match final pair".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            accumulator_lhs,
+            accumulator_rhs,
+            accumulator_limbs,
+            target_instances,
+        })
+    }
+
+    /// Checks the accumulated pairing `e(lhs, [s]_2) == e(rhs, [1]_2)` under `params`,
+    /// which must share the setup of the aggregated target params.
+    pub fn check_accumulator(&self, params: &ParamsKZG<Bn256>) -> bool {
+        Self::check_accumulators(std::slice::from_ref(self), params)
+    }
+
+    /// Checks the accumulated pairings of all `inputs` at once, on a random linear
+    /// combination of their accumulators.
+    pub fn check_accumulators(inputs: &[Self], params: &ParamsKZG<Bn256>) -> bool {
+        let (lhs, rhs) =
+            inputs
+                .iter()
+                .fold((G1::identity(), G1::identity()), |(lhs, rhs), input| {
+                    let r = Fr::random(OsRng);
+                    (
+                        lhs + input.accumulator_lhs * r,
+                        rhs + input.accumulator_rhs * r,
+                    )
+                });
+        let s_g2 = G2Prepared::from(params.s_g2());
+        let n_g2 = G2Prepared::from(-params.g2());
+        let (lhs, rhs) = (lhs.to_affine(), rhs.to_affine());
+        let result =
+            Bn256::multi_miller_loop(&[(&lhs, &s_g2), (&rhs, &n_g2)]).final_exponentiation();
+        bool::from(result.is_identity())
+    }
+
+    /// The PI circuit exposes the keccak hash of the block public data as its high and low
+    /// 128 bits. Returns `None` if the target instances are not laid out this way.
+    pub fn public_input_hash(&self) -> Option<H256> {
+        let [hi, lo] = self.target_instances.as_slice() else {
+            return None;
+        };
+        let mut hash = [0u8; 32];
+        for (half, value) in hash.chunks_mut(16).zip([hi, lo]) {
+            let le = value.to_bytes();
+            if le[16..].iter().any(|b| *b != 0) {
+                return None;
+            }
+            half.copy_from_slice(&le[..16]);
+            half.reverse();
+        }
+        Some(H256(hash))
+    }
+
+    /// Checks that the proof attests to `block_traces` by recomputing the SuperCircuit
+    /// instances from them, and returns the block-level values of the traces.
+    pub fn check_block_traces(&self, block_traces: &[BlockTrace]) -> Result<BlockPublicInputs> {
+        check_instances(&self.target_instances, block_traces, *CHAIN_ID)?;
+        BlockPublicInputs::from_block_traces(block_traces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProofPublicInputs;
+    use crate::io::{serialize_fr_tensor, serialize_verify_circuit_final_pair};
+    use halo2_proofs::halo2curves::bn256::{Bn256, Fr, G1Affine};
+    use halo2_snark_aggregator_circuit::verify_circuit::final_pair_to_instances;
+
+    #[test]
+    fn test_decode_public_inputs() {
+        let lhs = G1Affine::generator();
+        let rhs = -G1Affine::generator();
+        let hi = Fr::from(0x0102u64);
+        let lo = Fr::from(0x0304u64);
+        let pair = (lhs, rhs, vec![hi, lo]);
+
+        let final_pair = serialize_verify_circuit_final_pair(&pair);
+        let instances = final_pair_to_instances::<_, Bn256>(&pair);
+        let instance = serde_json::to_vec(&serialize_fr_tensor(&[vec![instances]])).unwrap();
+
+        let decoded = ProofPublicInputs::decode(&final_pair, &instance).unwrap();
+        assert_eq!(decoded.accumulator_lhs, lhs);
+        assert_eq!(decoded.accumulator_rhs, rhs);
+        assert_eq!(decoded.target_instances, vec![hi, lo]);
+
+        let hash = decoded.public_input_hash().unwrap();
+        assert_eq!(hash.0[14..16], [0x01, 0x02]);
+        assert_eq!(hash.0[30..32], [0x03, 0x04]);
+
+        let other = serialize_fr_tensor(&[vec![final_pair_to_instances::<_, Bn256>(&(
+            lhs,
+            rhs,
+            vec![lo, hi],
+        ))]]);
+        let other = serde_json::to_vec(&other).unwrap();
+        assert!(ProofPublicInputs::decode(&final_pair, &other).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::Cursor;
+
+use crate::circuit::{SuperCircuit, TargetCircuit, CIRCUIT_PROFILE};
+use crate::error::{Error, Result};
+use crate::vk_registry::AGG_VK_NAME;
+use halo2_proofs::arithmetic::Field;
+use halo2_proofs::halo2curves::bn256::{Bn256, Fr, G1Affine};
+use halo2_proofs::plonk::VerifyingKey;
+use halo2_proofs::poly::commitment::Params;
+use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+use halo2_proofs::SerdeFormat;
+use halo2_snark_aggregator_circuit::verify_circuit::{
+    final_pair_to_instances, Halo2VerifierCircuit,
+};
+use halo2_snark_aggregator_solidity::{MultiCircuitSolidityGenerate, SolidityGenerate};
+
+/// Number of target instances the agg circuit exposes, i.e. the hi and lo halves of the
+/// public input hash of the SuperCircuit.
+pub const TARGET_INSTANCE_LEN: usize = 2;
+
+/// Generates the verifier contract of the agg circuit. The generator only reads the layout
+/// of `proof` and `instance`, not their values.
+pub(crate) fn generate_verifier(
+    target_params: ParamsKZG<Bn256>,
+    target_vk: VerifyingKey<G1Affine>,
+    agg_params: &ParamsKZG<Bn256>,
+    agg_vk: &VerifyingKey<G1Affine>,
+    instance: Vec<Vec<Vec<Fr>>>,
+    proof: Vec<u8>,
+) -> String {
+    // NOTE: If any changes are made to circuit aggregation, targets should be reflected, too.
+    MultiCircuitSolidityGenerate {
+        target_circuits_params: [SolidityGenerate {
+            target_circuit_params: target_params,
+            target_circuit_vk: target_vk,
+            nproofs: 1,
+        }],
+        verify_vk: agg_vk,
+        verify_params: agg_params,
+        verify_circuit_instance: instance,
+        proof,
+        verify_public_inputs_size: 4, // not used now
+    }
+    .call("".into())
+}
+
+/// Generates the verifier contract from the serialized vks only, keyed by
+/// `TargetCircuit::name()` and [`AGG_VK_NAME`] as in a vk directory. The target params are
+/// derived from `agg_params`, and the agg proof and instance are replaced by placeholders
+/// of the same layout.
+pub fn generate_verifier_from_vks(
+    agg_params: &ParamsKZG<Bn256>,
+    vks: &HashMap<String, Vec<u8>>,
+) -> Result<String> {
+    let raw_vk = |name: &str| {
+        vks.get(name)
+            .ok_or_else(|| Error::VkNotFound(name.to_string()))
+    };
+    let target_vk = crate::with_super_circuit!(
+        *CIRCUIT_PROFILE,
+        C => VerifyingKey::<G1Affine>::read::<_, <C as TargetCircuit>::Inner>(
+            &mut Cursor::new(raw_vk(&C::name())?),
+            SerdeFormat::Processed,
+        )?
+    );
+    let agg_vk = VerifyingKey::<G1Affine>::read::<_, Halo2VerifierCircuit<'_, Bn256>>(
+        &mut Cursor::new(raw_vk(AGG_VK_NAME)?),
+        SerdeFormat::Processed,
+    )?;
+
+    let k = target_vk.get_domain().k();
+    if k > agg_params.k() {
+        return Err(Error::ParamsMismatch(format!(
+            "cannot derive target params of degree {} from agg params of degree {}",
+            k,
+            agg_params.k()
+        )));
+    }
+    let mut target_params = agg_params.clone();
+    target_params.downsize(k);
+
+    let instance = final_pair_to_instances::<_, Bn256>(&(
+        G1Affine::generator(),
+        G1Affine::generator(),
+        vec![Fr::zero(); TARGET_INSTANCE_LEN],
+    ));
+    Ok(generate_verifier(
+        target_params,
+        target_vk,
+        agg_params,
+        &agg_vk,
+        vec![vec![instance]],
+        placeholder_proof(&agg_vk),
+    ))
+}
+
+/// Placeholder of a GWC agg proof of `vk` written by `ShaWrite`, with the generator for
+/// every point and one for every scalar. Follows the transcript of `create_proof`, where
+/// KZG commits to instances without writing them.
+pub fn placeholder_proof(vk: &VerifyingKey<G1Affine>) -> Vec<u8> {
+    let cs = vk.cs();
+    let lookups = cs.lookups().len();
+    let permutation_columns = cs.permutation().get_columns().len();
+    let permutation_sets = if permutation_columns == 0 {
+        0
+    } else {
+        let chunk_len = cs.degree() - 2;
+        (permutation_columns + chunk_len - 1) / chunk_len
+    };
+
+    let points = cs.num_advice_columns()
+        + 2 * lookups
+        + permutation_sets
+        + lookups
+        // vanishing random poly and the pieces of the quotient poly
+        + 1
+        + (cs.degree() - 1);
+    let scalars = cs.advice_queries().len()
+        + cs.fixed_queries().len()
+        + 1
+        + permutation_columns
+        + (3 * permutation_sets).saturating_sub(1)
+        + 5 * lookups;
+
+    // GWC opens one witness per distinct rotation.
+    let mut rotations = BTreeSet::from([0]);
+    rotations.extend(cs.advice_queries().iter().map(|(_, r)| r.0));
+    rotations.extend(cs.fixed_queries().iter().map(|(_, r)| r.0));
+    if permutation_sets > 0 || lookups > 0 {
+        rotations.insert(1);
+    }
+    if permutation_sets > 1 {
+        rotations.insert(-((cs.blinding_factors() + 1) as i32));
+    }
+    if lookups > 0 {
+        rotations.insert(-1);
+    }
+
+    let generator = G1Affine::generator();
+    let point = [generator.x.to_bytes(), generator.y.to_bytes()].concat();
+    let scalar = Fr::one().to_bytes();
+    std::iter::repeat(point.as_slice())
+        .take(points)
+        .chain(std::iter::repeat(scalar.as_slice()).take(scalars))
+        .chain(std::iter::repeat(point.as_slice()).take(rotations.len()))
+        .flatten()
+        .copied()
+        .collect()
+}
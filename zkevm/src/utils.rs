@@ -1,4 +1,4 @@
-use anyhow::Result;
+use crate::error::{Error, Result};
 use halo2_proofs::arithmetic::Field;
 use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
 use halo2_proofs::halo2curves::FieldExt;
@@ -6,6 +6,7 @@ use halo2_proofs::SerdeFormat;
 
 use halo2_proofs::poly::kzg::commitment::ParamsKZG;
 use rand::rngs::OsRng;
+use rand::RngCore;
 use std::fs::{self, metadata, File};
 use std::io::{BufReader, Read, Write};
 use std::path::{Path, PathBuf};
@@ -56,7 +57,10 @@ pub fn create_kzg_params_to_file(params_dir: &str, degree: usize) {
 pub fn load_kzg_params(params_dir: &str, degree: usize) -> Result<ParamsKZG<Bn256>> {
     let params_path = format!("{params_dir}/params{degree}");
     if !Path::new(&params_path).exists() {
-        return Err(anyhow::format_err!("failed to load kzg params"));
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("failed to load kzg params: {params_path} not found"),
+        )));
     }
     log::info!("load_params {}", params_path);
     load_params(&params_path, degree, DEFAULT_SERDE_FORMAT)
@@ -93,7 +97,7 @@ pub fn load_params(
     let g2_bytes_len = 2 * g1_bytes_len;
     let expected_len = 4 + g1_num * g1_bytes_len + g2_num * g2_bytes_len;
     if file_size != expected_len {
-        return Err(anyhow::format_err!("invalid params file len {} for degree {}. check DEGREE or remove the invalid params file", file_size, degree));
+        return Err(Error::ParamsMismatch(format!("invalid params file len {} for degree {}. check DEGREE or remove the invalid params file", file_size, degree)));
     }
 
     let p = ParamsKZG::<Bn256>::read_custom::<_>(&mut BufReader::new(f), serde_format)?;
@@ -143,17 +147,14 @@ pub fn load_seed(seed_path: &str) -> Result<[u8; 16]> {
     Ok(seed)
 }
 
-/// create the seed and write it into file
+/// create the seed from OS randomness and write it into file
 pub fn create_seed(seed_path: &str) -> Result<[u8; 16]> {
-    // TODO: use better randomness source
-    const RNG_SEED_BYTES: [u8; 16] = [
-        0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
-        0xe5,
-    ];
+    let mut seed = [0u8; 16];
+    OsRng.fill_bytes(&mut seed);
 
     let mut seed_file = File::create(seed_path)?;
-    seed_file.write_all(RNG_SEED_BYTES.as_slice())?;
-    Ok(RNG_SEED_BYTES)
+    seed_file.write_all(seed.as_slice())?;
+    Ok(seed)
 }
 
 /// get a block-result from file
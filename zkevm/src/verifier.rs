@@ -1,28 +1,61 @@
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::path::Path;
 
 use crate::circuit::{TargetCircuit, AGG_DEGREE, DEGREE};
-use crate::io::{deserialize_fr_matrix, load_instances};
-use crate::prover::{AggCircuitProof, TargetCircuitProof};
+use crate::error::{Error, Result};
+use crate::io::{deserialize_fr_matrix, load_instances, vk_digest};
+use crate::prover::{AggCircuitProof, MultiopenScheme, TargetCircuitProof, TranscriptKind};
+use crate::public_inputs::ProofPublicInputs;
 use crate::utils::{load_params, DEFAULT_SERDE_FORMAT};
+use crate::vk_registry::{check_vk_digest, read_vk_dir, VkRegistryEntry, AGG_VK_NAME};
 use halo2_proofs::halo2curves::bn256::{Bn256, Fr, G1Affine};
 use halo2_proofs::plonk::VerifyingKey;
-use halo2_proofs::plonk::{keygen_vk, verify_proof};
-use halo2_proofs::poly::commitment::ParamsProver;
+use halo2_proofs::plonk::{self, keygen_vk, verify_proof};
+use halo2_proofs::poly::commitment::{Params, ParamsProver};
 use halo2_proofs::poly::kzg::commitment::ParamsKZG;
-use halo2_proofs::poly::kzg::multiopen::VerifierGWC;
-use halo2_proofs::poly::kzg::strategy::SingleStrategy;
-use halo2_proofs::transcript::{Challenge255, PoseidonRead};
+use halo2_proofs::poly::kzg::multiopen::{VerifierGWC, VerifierSHPLONK};
+use halo2_proofs::poly::kzg::strategy::{AccumulatorStrategy, SingleStrategy};
+use halo2_proofs::poly::VerificationStrategy;
+use halo2_proofs::transcript::{Challenge255, PoseidonRead, TranscriptRead};
 use halo2_snark_aggregator_api::transcript::sha::ShaRead;
 use halo2_snark_aggregator_circuit::verify_circuit::Halo2VerifierCircuit;
+use sha3::Keccak256;
+
+/// Verifies a KZG proof read from `transcript` with the given multiopen scheme.
+fn verify_kzg_proof<T>(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    instances: &[&[&[Fr]]],
+    multiopen: MultiopenScheme,
+    transcript: &mut T,
+) -> std::result::Result<(), plonk::Error>
+where
+    T: TranscriptRead<G1Affine, Challenge255<G1Affine>>,
+{
+    let strategy = SingleStrategy::new(params);
+    match multiopen {
+        MultiopenScheme::Gwc => {
+            verify_proof::<_, VerifierGWC<_>, _, _, _>(params, vk, strategy, instances, transcript)
+        }
+        MultiopenScheme::Shplonk => verify_proof::<_, VerifierSHPLONK<_>, _, _, _>(
+            params, vk, strategy, instances, transcript,
+        ),
+    }
+}
 
 pub struct Verifier {
-    params: ParamsKZG<Bn256>,
+    /// Params of the target circuits. Derived from `agg_params` on first use if not given.
+    params: Option<ParamsKZG<Bn256>>,
     agg_params: ParamsKZG<Bn256>,
     // just for legacy testing code...
     raw_agg_vk: Option<Vec<u8>>,
     agg_vk: Option<VerifyingKey<G1Affine>>,
     target_circuit_vks: HashMap<String, VerifyingKey<G1Affine>>,
+    /// Serialized target vks, parsed on first use of each circuit.
+    raw_target_circuit_vks: HashMap<String, Vec<u8>>,
+    /// Whether a target vk missing from `raw_target_circuit_vks` is generated by keygen.
+    allow_keygen: bool,
 }
 
 impl Verifier {
@@ -32,7 +65,7 @@ impl Verifier {
         raw_agg_vk: Option<Vec<u8>>,
     ) -> Self {
         if raw_agg_vk.is_none() {
-            log::error!("Verifier should better have raw_agg_vk to check consistency");
+            log::error!("Verifier without raw_agg_vk rejects every agg proof");
         }
         let agg_vk = raw_agg_vk.as_ref().map(|k| {
             VerifyingKey::<G1Affine>::read::<_, Halo2VerifierCircuit<'_, Bn256>>(
@@ -43,14 +76,79 @@ impl Verifier {
         });
 
         Self {
-            params,
+            params: Some(params),
             agg_params,
             agg_vk,
             raw_agg_vk,
             target_circuit_vks: Default::default(),
+            raw_target_circuit_vks: Default::default(),
+            allow_keygen: true,
         }
     }
 
+    /// Creates a verifier from the vks in `vk_dir`, see [`read_vk_dir`]. Each vk must match
+    /// its hex sha256 digest in `digests`, keyed by `TargetCircuit::name()` or
+    /// [`AGG_VK_NAME`]. Target vks are never generated by keygen, and target params are
+    /// derived from `agg_params` if `params` is `None`.
+    pub fn from_vk_dir(
+        params: Option<ParamsKZG<Bn256>>,
+        agg_params: ParamsKZG<Bn256>,
+        vk_dir: impl AsRef<Path>,
+        digests: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let mut raw_target_circuit_vks = read_vk_dir(vk_dir)?;
+        for (name, vk) in raw_target_circuit_vks.iter() {
+            check_vk_digest(name, vk, digests)?;
+            log::info!("loaded vk of {} circuit", name);
+        }
+        let raw_agg_vk = raw_target_circuit_vks
+            .remove(AGG_VK_NAME)
+            .ok_or_else(|| Error::VkNotFound(AGG_VK_NAME.to_string()))?;
+        let agg_vk = VerifyingKey::<G1Affine>::read::<_, Halo2VerifierCircuit<'_, Bn256>>(
+            &mut Cursor::new(&raw_agg_vk),
+            halo2_proofs::SerdeFormat::Processed,
+        )?;
+
+        Ok(Self {
+            params,
+            agg_params,
+            agg_vk: Some(agg_vk),
+            raw_agg_vk: Some(raw_agg_vk),
+            target_circuit_vks: Default::default(),
+            raw_target_circuit_vks,
+            allow_keygen: false,
+        })
+    }
+
+    /// Same as [`Verifier::from_vk_dir`], with the digests of a registry entry.
+    pub fn from_registry(
+        params: Option<ParamsKZG<Bn256>>,
+        agg_params: ParamsKZG<Bn256>,
+        vk_dir: impl AsRef<Path>,
+        entry: &VkRegistryEntry,
+    ) -> Result<Self> {
+        Self::from_vk_dir(params, agg_params, vk_dir, &entry.digests)
+    }
+
+    /// Returns the target params of degree `k`, downsizing `agg_params` if no target params
+    /// were given. Every params share the same setup, see `Prover::new`.
+    fn target_params(&mut self, k: u32) -> Result<&ParamsKZG<Bn256>> {
+        if self.params.is_none() {
+            if k > self.agg_params.k() {
+                return Err(Error::ParamsMismatch(format!(
+                    "cannot derive target params of degree {} from agg params of degree {}",
+                    k,
+                    self.agg_params.k()
+                )));
+            }
+            log::info!("derive target params of degree {} from agg params", k);
+            let mut params = self.agg_params.clone();
+            params.downsize(k);
+            self.params = Some(params);
+        }
+        Ok(self.params.as_ref().unwrap())
+    }
+
     pub fn from_params(
         params: ParamsKZG<Bn256>,
         agg_params: ParamsKZG<Bn256>,
@@ -67,22 +165,47 @@ impl Verifier {
         Self::from_params(params, agg_params, agg_vk)
     }
 
-    pub fn verify_agg_circuit_proof(&self, proof: AggCircuitProof) -> anyhow::Result<()> {
-        if let Some(raw_agg_vk) = &self.raw_agg_vk {
-            if &proof.vk != raw_agg_vk {
-                log::error!(
-                    "vk provided in proof != vk in verifier, proof vk {:?}... vs config vk {:?}...",
-                    &proof.vk[..10],
-                    &raw_agg_vk[..10]
-                );
-            }
-        }
-        let verify_circuit_instance: Vec<Vec<Vec<Fr>>> = {
-            let instance = proof.instance;
-            load_instances(&instance)
+    /// Checks `proof` against the configured agg vk and its instances against its final
+    /// pair, and returns the instances with the decoded final pair. The accumulator of the
+    /// final pair is left to the caller to check.
+    fn check_agg_proof(
+        &self,
+        proof: &AggCircuitProof,
+    ) -> Result<(
+        &VerifyingKey<G1Affine>,
+        Vec<Vec<Vec<Fr>>>,
+        ProofPublicInputs,
+    )> {
+        // The vk in the proof is untrusted, so it is only checked against the configured one.
+        let (raw_agg_vk, agg_vk) = match (&self.raw_agg_vk, &self.agg_vk) {
+            (Some(raw_agg_vk), Some(agg_vk)) => (raw_agg_vk, agg_vk),
+            _ => return Err(Error::VkNotFound(AGG_VK_NAME.to_string())),
         };
+        if &proof.vk != raw_agg_vk {
+            return Err(Error::VkDigestMismatch {
+                circuit: AGG_VK_NAME.to_string(),
+                expected: vk_digest(raw_agg_vk),
+                actual: vk_digest(&proof.vk),
+            });
+        }
+        let transcript_kind = proof.metadata.transcript.unwrap_or(TranscriptKind::Sha256);
+        if transcript_kind != TranscriptKind::Sha256 {
+            return Err(Error::Unsupported(format!(
+                "{transcript_kind} transcript agg proof"
+            )));
+        }
+        let public_inputs = ProofPublicInputs::from_agg_proof(proof)?;
+        Ok((agg_vk, load_instances(&proof.instance), public_inputs))
+    }
+
+    pub fn verify_agg_circuit_proof(&self, proof: AggCircuitProof) -> Result<()> {
+        let (agg_vk, verify_circuit_instance, public_inputs) = self.check_agg_proof(&proof)?;
+        if !public_inputs.check_accumulator(&self.agg_params) {
+            return Err(Error::InvalidProof(
+                "final pair accumulator fails pairing check".to_string(),
+            ));
+        }
         let params = self.agg_params.verifier_params();
-        let strategy = SingleStrategy::new(params);
 
         let verify_circuit_instance1: Vec<Vec<&[Fr]>> = verify_circuit_instance
             .iter()
@@ -92,49 +215,147 @@ impl Verifier {
             verify_circuit_instance1.iter().map(|x| &x[..]).collect();
 
         let mut transcript = ShaRead::<_, _, Challenge255<_>, sha2::Sha256>::init(&proof.proof[..]);
-
-        // TODO better way to do this?
-        let vk_in_proof = VerifyingKey::<G1Affine>::read::<_, Halo2VerifierCircuit<'_, Bn256>>(
-            &mut Cursor::new(&proof.vk),
-            halo2_proofs::SerdeFormat::Processed,
-        )?;
-        verify_proof::<_, VerifierGWC<_>, _, _, _>(
+        verify_kzg_proof(
             params,
-            self.agg_vk.as_ref().unwrap_or(&vk_in_proof),
-            strategy,
+            agg_vk,
             &verify_circuit_instance2[..],
+            proof.metadata.multiopen,
             &mut transcript,
-        )?;
+        )
+        .map_err(Error::verification("agg"))?;
+        Ok(())
+    }
+
+    /// Verifies `proofs` with a single final pairing check. The msms of all proofs are
+    /// accumulated with random scalars, so a failure does not tell which proof is invalid;
+    /// use [`Verifier::verify_agg_circuit_proof`] on each of them to find it. Fails on an
+    /// empty `proofs`, which would otherwise pass without checking anything.
+    pub fn verify_agg_circuit_proofs(&self, proofs: &[AggCircuitProof]) -> Result<()> {
+        if proofs.is_empty() {
+            return Err(Error::InvalidProof("no agg proofs to verify".to_string()));
+        }
+        let params = self.agg_params.verifier_params();
+        let mut strategy = AccumulatorStrategy::new(params);
+        let mut public_inputs = Vec::with_capacity(proofs.len());
+        for proof in proofs {
+            let (agg_vk, instances, proof_public_inputs) = self.check_agg_proof(proof)?;
+            public_inputs.push(proof_public_inputs);
+            let instances1: Vec<Vec<&[Fr]>> = instances
+                .iter()
+                .map(|x| x.iter().map(|y| &y[..]).collect())
+                .collect();
+            let instances2: Vec<&[&[Fr]]> = instances1.iter().map(|x| &x[..]).collect();
+
+            let mut transcript =
+                ShaRead::<_, _, Challenge255<_>, sha2::Sha256>::init(&proof.proof[..]);
+            strategy = match proof.metadata.multiopen {
+                MultiopenScheme::Gwc => verify_proof::<_, VerifierGWC<_>, _, _, _>(
+                    params,
+                    agg_vk,
+                    strategy,
+                    &instances2[..],
+                    &mut transcript,
+                ),
+                MultiopenScheme::Shplonk => verify_proof::<_, VerifierSHPLONK<_>, _, _, _>(
+                    params,
+                    agg_vk,
+                    strategy,
+                    &instances2[..],
+                    &mut transcript,
+                ),
+            }
+            .map_err(Error::verification("agg"))?;
+        }
+        if !ProofPublicInputs::check_accumulators(&public_inputs, &self.agg_params) {
+            return Err(Error::InvalidProof(
+                "final pair accumulator fails pairing check".to_string(),
+            ));
+        }
+        if !VerificationStrategy::<_, VerifierGWC<_>>::finalize(strategy) {
+            return Err(Error::InvalidProof(
+                "batched final pairing check failed".to_string(),
+            ));
+        }
         Ok(())
     }
 
     pub fn verify_target_circuit_proof<C: TargetCircuit>(
         &mut self,
         proof: &TargetCircuitProof,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let instances: Vec<Vec<Vec<u8>>> = serde_json::from_reader(&proof.instance[..])?;
         let instances = deserialize_fr_matrix(instances);
 
         let instance_slice = instances.iter().map(|x| &x[..]).collect::<Vec<_>>();
 
-        let verifier_params = self.params.verifier_params();
+        if !self.target_circuit_vks.contains_key(&C::name()) {
+            let vk = match self.raw_target_circuit_vks.get(&C::name()) {
+                Some(raw_vk) => VerifyingKey::<G1Affine>::read::<_, C::Inner>(
+                    &mut Cursor::new(raw_vk),
+                    halo2_proofs::SerdeFormat::Processed,
+                )?,
+                None if self.allow_keygen => {
+                    let params = self.target_params(*DEGREE as u32)?;
+                    keygen_vk(params, &C::empty()).map_err(Error::keygen(C::name()))?
+                }
+                None => return Err(Error::VkNotFound(C::name())),
+            };
+            self.target_circuit_vks.insert(C::name(), vk);
+        }
+        let k = self.target_circuit_vks[&C::name()].get_domain().k();
+        self.target_params(k)?;
+        let verifier_params = self.params.as_ref().unwrap().verifier_params();
+        let vk = &self.target_circuit_vks[&C::name()];
 
-        let mut transcript = PoseidonRead::<_, _, Challenge255<_>>::init(&proof.proof[..]);
-        let strategy = SingleStrategy::new(verifier_params);
+        let instances: &[&[&[Fr]]] = &[instance_slice.as_slice()];
+        let multiopen = proof.metadata.multiopen;
+        match proof
+            .metadata
+            .transcript
+            .unwrap_or(TranscriptKind::Poseidon)
+        {
+            TranscriptKind::Poseidon => verify_kzg_proof(
+                verifier_params,
+                vk,
+                instances,
+                multiopen,
+                &mut PoseidonRead::<_, _, Challenge255<_>>::init(&proof.proof[..]),
+            ),
+            TranscriptKind::Sha256 => verify_kzg_proof(
+                verifier_params,
+                vk,
+                instances,
+                multiopen,
+                &mut ShaRead::<_, _, Challenge255<_>, sha2::Sha256>::init(&proof.proof[..]),
+            ),
+            TranscriptKind::Keccak => verify_kzg_proof(
+                verifier_params,
+                vk,
+                instances,
+                multiopen,
+                &mut ShaRead::<_, _, Challenge255<_>, Keccak256>::init(&proof.proof[..]),
+            ),
+        }
+        .map_err(Error::verification(C::name()))?;
+        Ok(())
+    }
+}
 
-        let vk = self.target_circuit_vks.entry(C::name()).or_insert_with(|| {
-            let circuit = C::empty();
-            keygen_vk(&self.params, &circuit)
-                .unwrap_or_else(|_| panic!("failed to generate {} vk", C::name()))
-        });
+#[cfg(test)]
+mod tests {
+    use super::Verifier;
+    use crate::error::Error;
+    use halo2_proofs::halo2curves::bn256::Bn256;
+    use halo2_proofs::poly::commitment::ParamsProver;
+    use halo2_proofs::poly::kzg::commitment::ParamsKZG;
 
-        verify_proof::<_, VerifierGWC<_>, _, _, _>(
-            verifier_params,
-            vk,
-            strategy,
-            &[instance_slice.as_slice()],
-            &mut transcript,
-        )?;
-        Ok(())
+    #[test]
+    fn test_verify_no_agg_proofs() {
+        let params = ParamsKZG::<Bn256>::new(4);
+        let verifier = Verifier::new(params.clone(), params, None);
+        assert!(matches!(
+            verifier.verify_agg_circuit_proofs(&[]),
+            Err(Error::InvalidProof(_))
+        ));
     }
 }
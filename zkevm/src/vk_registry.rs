@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::circuit::{AGG_DEGREE, CIRCUIT_PROFILE, DEGREE};
+use crate::error::{Error, Result};
+use crate::io::vk_digest;
+use crate::utils::read_env_var;
+use crate::version;
+use once_cell::sync::Lazy;
+use serde_derive::{Deserialize, Serialize};
+
+/// Path of the vk registry, read from `VK_REGISTRY_PATH`.
+pub static VK_REGISTRY_PATH: Lazy<String> =
+    Lazy::new(|| read_env_var("VK_REGISTRY_PATH", "vk_registry.json".to_string()));
+
+/// Name of the agg circuit in vk digests.
+pub const AGG_VK_NAME: &str = "agg";
+/// File of the agg vk in a vk directory, as written by `AggCircuitProof::write_to_dir`.
+pub const AGG_VK_FILE: &str = "verify_circuit.vkey";
+
+/// Circuit parameters the vks depend on besides the prover version.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitConfig {
+    pub degree: u32,
+    pub agg_degree: u32,
+    pub max_txs: u32,
+    pub max_calldata: u32,
+}
+
+impl CircuitConfig {
+    /// Config of this build and environment.
+    pub fn current() -> Self {
+        Self {
+            degree: *DEGREE as u32,
+            agg_degree: *AGG_DEGREE as u32,
+            max_txs: CIRCUIT_PROFILE.max_txs as u32,
+            max_calldata: CIRCUIT_PROFILE.max_calldata as u32,
+        }
+    }
+}
+
+/// Vk digests of one prover version and circuit config, keyed by `TargetCircuit::name()`
+/// and [`AGG_VK_NAME`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VkRegistryEntry {
+    pub prover_version: String,
+    pub config: CircuitConfig,
+    pub digests: HashMap<String, String>,
+}
+
+impl VkRegistryEntry {
+    /// Checks `vk` against the digest registered for the `name` circuit.
+    pub fn check(&self, name: &str, vk: &[u8]) -> Result<()> {
+        check_vk_digest(name, vk, &self.digests)
+    }
+}
+
+/// Published vk digests, so that provers and verifiers can pin the same keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VkRegistry {
+    pub entries: Vec<VkRegistryEntry>,
+}
+
+impl VkRegistry {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, prover_version: &str, config: &CircuitConfig) -> Option<&VkRegistryEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.prover_version == prover_version && &e.config == config)
+    }
+
+    /// Entry of this prover version and [`CircuitConfig::current`].
+    pub fn current(&self) -> Result<&VkRegistryEntry> {
+        let prover_version = version::as_string();
+        let config = CircuitConfig::current();
+        self.get(&prover_version, &config).ok_or_else(|| {
+            Error::VkNotFound(format!(
+                "registry entry of prover {prover_version} with {config:?}"
+            ))
+        })
+    }
+
+    /// Registers the digests of `vks`, replacing the entry of the same version and config.
+    pub fn register(
+        &mut self,
+        prover_version: &str,
+        config: CircuitConfig,
+        vks: &HashMap<String, Vec<u8>>,
+    ) {
+        let digests = vks
+            .iter()
+            .map(|(name, vk)| (name.clone(), vk_digest(vk)))
+            .collect();
+        self.entries
+            .retain(|e| !(e.prover_version == prover_version && e.config == config));
+        self.entries.push(VkRegistryEntry {
+            prover_version: prover_version.to_string(),
+            config,
+            digests,
+        });
+    }
+}
+
+pub(crate) fn check_vk_digest(
+    name: &str,
+    vk: &[u8],
+    digests: &HashMap<String, String>,
+) -> Result<()> {
+    let expected = digests
+        .get(name)
+        .ok_or_else(|| Error::VkNotFound(format!("{name} digest")))?;
+    let actual = vk_digest(vk);
+    if !expected
+        .trim_start_matches("0x")
+        .eq_ignore_ascii_case(&actual)
+    {
+        return Err(Error::VkDigestMismatch {
+            circuit: name.to_string(),
+            expected: expected.clone(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Reads the vks in `vk_dir` by circuit name: `{name}.vk` for every target circuit, as the
+/// prover writes them into its `debug_dir`, and [`AGG_VK_FILE`] for the agg circuit.
+pub fn read_vk_dir(vk_dir: impl AsRef<Path>) -> Result<HashMap<String, Vec<u8>>> {
+    let mut vks = HashMap::new();
+    for entry in std::fs::read_dir(vk_dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = if path.file_name().and_then(|n| n.to_str()) == Some(AGG_VK_FILE) {
+            AGG_VK_NAME.to_string()
+        } else if path.extension().and_then(|e| e.to_str()) == Some("vk") {
+            match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) => stem.to_string(),
+                None => continue,
+            }
+        } else {
+            continue;
+        };
+        vks.insert(name, std::fs::read(&path)?);
+    }
+    Ok(vks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CircuitConfig, VkRegistry, AGG_VK_NAME};
+    use crate::error::Error;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_vk_registry() {
+        let config = CircuitConfig {
+            degree: 21,
+            agg_degree: 26,
+            max_txs: 100,
+            max_calldata: 2_000_000,
+        };
+        let vks = HashMap::from([
+            ("super".to_string(), vec![1u8, 2, 3]),
+            (AGG_VK_NAME.to_string(), vec![4u8, 5, 6]),
+        ]);
+        let mut registry = VkRegistry::default();
+        registry.register("0.1.5", config.clone(), &vks);
+        registry.register("0.1.5", config.clone(), &vks);
+        assert_eq!(registry.entries.len(), 1);
+
+        let json = serde_json::to_vec(&registry).unwrap();
+        let registry: VkRegistry = serde_json::from_slice(&json).unwrap();
+        let entry = registry.get("0.1.5", &config).unwrap();
+        entry.check("super", &[1, 2, 3]).unwrap();
+        assert!(matches!(
+            entry.check(AGG_VK_NAME, &[1, 2, 3]),
+            Err(Error::VkDigestMismatch { .. })
+        ));
+        assert!(matches!(
+            entry.check("evm", &[1, 2, 3]),
+            Err(Error::VkNotFound(_))
+        ));
+
+        let other = CircuitConfig {
+            degree: 19,
+            ..config.clone()
+        };
+        assert!(registry.get("0.1.5", &other).is_none());
+        assert!(registry.get("0.1.4", &config).is_none());
+    }
+}
@@ -1,18 +1,13 @@
-use halo2_proofs::halo2curves::bn256::{Bn256, G1Affine};
-use halo2_proofs::plonk::VerifyingKey;
-use halo2_proofs::SerdeFormat;
-
-use halo2_snark_aggregator_circuit::verify_circuit::Halo2VerifierCircuit;
-use halo2_snark_aggregator_solidity::MultiCircuitSolidityGenerate;
 use std::fs::{self};
-use std::io::Cursor;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use zkevm::circuit::{SuperCircuit, AGG_DEGREE, DEGREE};
 use zkevm::prover::{AggCircuitProof, ProvedCircuit};
+use zkevm::solidity::{generate_verifier_from_vks, placeholder_proof};
 use zkevm::utils::{load_or_create_params, load_seed};
 use zkevm::verifier::Verifier;
+use zkevm::vk_registry::read_vk_dir;
 use zkevm::{io::*, prover::Prover};
 
 mod test_util;
@@ -44,7 +39,7 @@ fn verifier_circuit_prove(output_dir: &str) {
     };
 
     let agg_proof = prover
-        .create_agg_circuit_proof_impl(circuit_results)
+        .create_agg_circuit_proof_impl(circuit_results, true)
         .unwrap();
     agg_proof.write_to_dir(&mut out_dir);
     log::info!("output files to {}", output_dir);
@@ -54,34 +49,52 @@ fn verifier_circuit_prove(output_dir: &str) {
 fn verifier_circuit_generate_solidity(dir: &str) {
     let mut folder = PathBuf::from_str(dir).unwrap();
 
+    // Only the vks written by `verifier_circuit_prove` are needed, not the proof.
     let params = load_or_create_params(PARAMS_DIR, *AGG_DEGREE).unwrap();
-    let load_full = true;
-    let (vk, proof, instance) = if load_full {
-        let file = fs::File::open(format!("{dir}/full_proof.data")).unwrap();
-        let agg_proof: AggCircuitProof = serde_json::from_reader(file).unwrap();
-        (agg_proof.vk, agg_proof.proof, agg_proof.instance)
-    } else {
-        (
-            load_verify_circuit_vk(&mut folder),
-            load_verify_circuit_proof(&mut folder),
-            load_verify_circuit_instance(&mut folder),
-        )
-    };
-    let vk = VerifyingKey::<G1Affine>::read::<_, Halo2VerifierCircuit<'_, Bn256>>(
-        &mut Cursor::new(&vk),
+    let vks = read_vk_dir(dir).unwrap();
+    let sol = generate_verifier_from_vks(&params, &vks).unwrap();
+    write_verify_circuit_solidity(&mut folder, &Vec::<u8>::from(sol.as_bytes()));
+    log::info!("write to {}/verifier.sol", dir);
+}
+
+#[cfg(feature = "prove_verify")]
+#[test]
+fn test_placeholder_proof_layout() {
+    use halo2_proofs::halo2curves::bn256::{Bn256, G1Affine};
+    use halo2_proofs::plonk::VerifyingKey;
+    use halo2_proofs::SerdeFormat;
+    use halo2_snark_aggregator_circuit::verify_circuit::Halo2VerifierCircuit;
+
+    init();
+    let dir = std::env::temp_dir().join(format!("placeholder_proof_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let params = load_or_create_params(PARAMS_DIR, *DEGREE).unwrap();
+    let agg_params = load_or_create_params(PARAMS_DIR, *AGG_DEGREE).unwrap();
+    let seed = load_seed(SEED_PATH).unwrap();
+    let mut prover = Prover::from_params_and_seed(params, agg_params.clone(), seed);
+    prover.debug_dir = dir.to_str().unwrap().to_string();
+
+    let block_traces = load_block_traces_for_test().1;
+    let circuit_results = vec![prover.prove_circuit::<SuperCircuit>(&block_traces).unwrap()];
+    let agg_proof = prover
+        .create_agg_circuit_proof_impl(circuit_results, true)
+        .unwrap();
+    agg_proof.write_to_dir(&mut dir.clone());
+
+    let agg_vk = VerifyingKey::<G1Affine>::read::<_, Halo2VerifierCircuit<'_, Bn256>>(
+        &mut std::io::Cursor::new(&agg_proof.vk),
         SerdeFormat::Processed,
     )
     .unwrap();
-    let request = MultiCircuitSolidityGenerate {
-        verify_vk: &vk,
-        verify_params: &params,
-        verify_circuit_instance: load_instances(&instance),
-        proof,
-        verify_public_inputs_size: 4,
-    };
-    let sol = request.call("".into());
-    write_verify_circuit_solidity(&mut folder, &Vec::<u8>::from(sol.as_bytes()));
-    log::info!("write to {}/verifier.sol", dir);
+    assert_eq!(placeholder_proof(&agg_vk).len(), agg_proof.proof.len());
+
+    // The generator reads only the layout of the proof, so the vks alone give the same contract.
+    let vks = read_vk_dir(&dir).unwrap();
+    let from_vks = generate_verifier_from_vks(&agg_params, &vks).unwrap();
+    let from_proof = prover.create_solidity_verifier(&agg_proof).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(from_vks == from_proof, "verifier contracts differ");
 }
 
 #[cfg(feature = "prove_verify")]
@@ -93,7 +106,8 @@ fn verifier_circuit_verify_proof() {
     let proof = read_env_var("PROOF_JSON", "proof.json".to_string());
     let file = fs::File::open(proof).unwrap();
     let agg_proof: AggCircuitProof = serde_json::from_reader(file).unwrap();
-    let verifier = Verifier::from_fpath(PARAMS_DIR, None);
+    // Pins the vk of the proof itself, as the verifier no longer falls back to it.
+    let verifier = Verifier::from_fpath(PARAMS_DIR, Some(agg_proof.vk.clone()));
     assert!(verifier.verify_agg_circuit_proof(agg_proof).is_ok())
 }
 
@@ -107,15 +121,25 @@ fn verifier_circuit_verify(d: &str) {
 
     let proof = load_verify_circuit_proof(&mut folder);
     let instance = load_verify_circuit_instance(&mut folder);
+    let final_pair = load_verify_circuit_final_pair(&mut folder);
 
     let agg_proof = AggCircuitProof {
         proof,
         instance,
         vk,
-        final_pair: vec![], // not used
-        block_count: 0,     // not used
+        final_pair,
+        block_count: 0, // not used
+        metadata: Default::default(),
     };
+    let agg_proofs = [agg_proof.clone(), agg_proof];
+    verifier.verify_agg_circuit_proofs(&agg_proofs).unwrap();
+    let [agg_proof, mut tampered] = agg_proofs;
     verifier.verify_agg_circuit_proof(agg_proof).unwrap();
+
+    // Swapping the accumulator points keeps them on the curve but breaks the instances.
+    let (lhs, rhs) = tampered.final_pair[..128].split_at_mut(64);
+    lhs.swap_with_slice(rhs);
+    assert!(verifier.verify_agg_circuit_proof(tampered).is_err());
 }
 
 #[cfg(feature = "prove_verify")]
@@ -0,0 +1,120 @@
+//! Runs the solidity verifier generated by `test_agg` in an in-process EVM.
+//!
+//! `OUTPUT_DIR` must hold the `full_proof.data` and `verifier.sol` written by `test_agg`.
+//! The verifier is deployed from `verifier.bin` in the same dir if it exists, e.g. a
+//! committed fixture, and compiled with `solc` otherwise.
+
+#[cfg(feature = "prove_verify")]
+mod test_util;
+
+#[cfg(feature = "prove_verify")]
+mod evm {
+    use revm::primitives::{Bytes, CreateScheme, ExecutionResult, Output, TransactTo, B160, U256};
+    use revm::{InMemoryDB, EVM};
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Deployment bytecode of the verifier in `dir`.
+    pub fn load_verifier_bytecode(dir: &str) -> Vec<u8> {
+        let fixture = Path::new(dir).join("verifier.bin");
+        let hex_code = if fixture.exists() {
+            std::fs::read_to_string(fixture).unwrap()
+        } else {
+            let sol = Path::new(dir).join("verifier.sol");
+            let output = Command::new("solc")
+                .args(["--bin", "--optimize"])
+                .arg(&sol)
+                .output()
+                .expect("solc is required to compile verifier.sol");
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            // Every contract is followed by its binary, and the verifier is the largest one.
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            stdout
+                .split("Binary:")
+                .skip(1)
+                .filter_map(|s| s.split_whitespace().next())
+                .max_by_key(|code| code.len())
+                .expect("no contract in verifier.sol")
+                .to_string()
+        };
+        hex::decode(hex_code.trim().trim_start_matches("0x")).unwrap()
+    }
+
+    pub struct VerifierEvm {
+        evm: EVM<InMemoryDB>,
+        address: B160,
+    }
+
+    impl VerifierEvm {
+        pub fn deploy(bytecode: Vec<u8>) -> Self {
+            let mut evm = EVM::new();
+            evm.database(InMemoryDB::default());
+            // The verifier exceeds the EIP-170 code size limit of mainnet.
+            evm.env.cfg.limit_contract_code_size = Some(usize::MAX);
+            evm.env.tx.caller = B160::from_low_u64_be(0xfe);
+            evm.env.tx.gas_limit = u64::MAX;
+            evm.env.tx.value = U256::ZERO;
+            evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+            evm.env.tx.data = Bytes::from(bytecode);
+            let address = match evm.transact_commit().unwrap() {
+                ExecutionResult::Success {
+                    output: Output::Create(_, Some(address)),
+                    ..
+                } => address,
+                result => panic!("failed to deploy verifier: {result:?}"),
+            };
+            Self { evm, address }
+        }
+
+        /// Calls the verifier and returns whether it accepted the proof, with the gas used.
+        pub fn call(&mut self, calldata: Vec<u8>) -> (bool, u64) {
+            self.evm.env.tx.transact_to = TransactTo::Call(self.address);
+            self.evm.env.tx.data = Bytes::from(calldata);
+            match self.evm.transact_ref().unwrap().result {
+                ExecutionResult::Success {
+                    output: Output::Call(output),
+                    gas_used,
+                    ..
+                } => (output.last() == Some(&1), gas_used),
+                ExecutionResult::Revert { gas_used, .. } => (false, gas_used),
+                ExecutionResult::Halt { gas_used, .. } => (false, gas_used),
+                result => panic!("unexpected result: {result:?}"),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "prove_verify")]
+#[test]
+fn test_evm_verifier() {
+    use evm::{load_verifier_bytecode, VerifierEvm};
+    use test_util::init;
+    use zkevm::prover::AggCircuitProof;
+    use zkevm::utils::read_env_var;
+
+    init();
+    let dir = read_env_var("OUTPUT_DIR", "output".to_string());
+    let file = std::fs::File::open(format!("{dir}/full_proof.data")).unwrap();
+    let agg_proof: AggCircuitProof = serde_json::from_reader(file).unwrap();
+
+    let mut verifier = VerifierEvm::deploy(load_verifier_bytecode(&dir));
+    let calldata = agg_proof.verifier_calldata(true).unwrap();
+    let (accepted, gas_used) = verifier.call(calldata.clone());
+    log::info!("verifier gas used: {}", gas_used);
+    assert!(accepted, "verifier rejected a valid proof");
+
+    // Every word of the calldata is a uint256, so flipping a low bit of the first proof
+    // word and of the last final pair word keeps it well-formed.
+    let proof_word = 4 + 32 * 3;
+    for end in [proof_word + 32, calldata.len()] {
+        let mut mutated = calldata.clone();
+        mutated[end - 1] ^= 1;
+        let (accepted, gas_used) = verifier.call(mutated);
+        log::info!("verifier gas used on mutated proof: {}", gas_used);
+        assert!(!accepted, "verifier accepted a mutated proof");
+    }
+}
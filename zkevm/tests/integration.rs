@@ -2,10 +2,14 @@ use chrono::Utc;
 use halo2_proofs::{plonk::keygen_vk, SerdeFormat};
 
 use zkevm::{
-    circuit::{SuperCircuit, TargetCircuit, DEGREE},
+    circuit::{
+        calculate_row_usage_of_trace, estimate_row_usage_of_trace, target_circuit,
+        validate_block_trace, DynTargetCircuit, SuperCircuit, TargetCircuit, DEGREE,
+        ESTIMATE_ERROR_BOUND, SUB_CIRCUIT_NAMES,
+    },
     io::serialize_vk,
     prover::Prover,
-    utils::{load_or_create_params, load_params},
+    utils::{get_block_trace_from_file, load_or_create_params, load_params},
 };
 
 mod test_util;
@@ -15,6 +19,44 @@ use once_cell::sync::Lazy;
 use zkevm::utils::read_env_var;
 pub static CIRCUIT: Lazy<String> = Lazy::new(|| read_env_var("CIRCUIT", "super".to_string()));
 
+#[test]
+fn test_type2_fee_caps() {
+    let trace = get_block_trace_from_file("./tests/traces/kroma/type2_gasprice_accesslist.json");
+    validate_block_trace(&trace).unwrap();
+
+    let tx = trace.transactions[1].to_eth_tx(None, None, None);
+    assert_eq!(tx.max_fee_per_gas, Some(0x59682f02.into()));
+    assert_eq!(tx.max_priority_fee_per_gas, Some(0x59682f00.into()));
+    assert_eq!(tx.gas_price, Some(0x59682f01.into()));
+}
+
+#[test]
+fn test_batch_of_unchained_blocks() {
+    use zkevm::circuit::validate_batch;
+    use zkevm::Error;
+
+    let trace = get_block_trace_from_file("./tests/traces/kroma/multiple_transfers_0.json");
+    assert_ne!(
+        trace.storage_trace.root_before,
+        trace.storage_trace.root_after
+    );
+
+    // Even as a child of itself, the block does not start from the state it left.
+    let mut child = trace.clone();
+    child.header.number = Some(trace.header.number.unwrap() + 1u64);
+    child.header.parent_hash = trace.header.hash.unwrap();
+    assert!(matches!(
+        validate_batch(&[trace.clone(), child]),
+        Err(Error::BlockNotChained { .. })
+    ));
+
+    let other = get_block_trace_from_file("./tests/traces/kroma/type2_gasprice_accesslist.json");
+    assert!(matches!(
+        validate_batch(&[trace, other]),
+        Err(Error::BlockNotChained { .. })
+    ));
+}
+
 #[ignore]
 #[test]
 fn test_load_params() {
@@ -43,21 +85,15 @@ fn test_load_params() {
 #[ignore]
 #[test]
 fn estimate_circuit_rows() {
-    use zkevm::circuit::{self, TargetCircuit};
-
     init();
 
     let (_, block_trace) = load_block_traces_for_test();
 
     log::info!("estimating used rows for batch");
     for circuit in CIRCUIT.split(",") {
-        let rows = match circuit {
-            "evm" => circuit::EvmCircuit::estimate_rows(&block_trace),
-            "state" => circuit::StateCircuit::estimate_rows(&block_trace),
-            "zktrie" => circuit::ZktrieCircuit::estimate_rows(&block_trace),
-            "poseidon" => circuit::PoseidonCircuit::estimate_rows(&block_trace),
-            "super" => circuit::SuperCircuit::estimate_rows(&block_trace),
-            _ => {
+        let rows = match target_circuit(circuit) {
+            Some(c) => c.estimate_rows(&block_trace),
+            None => {
                 unimplemented!("invalid circuit: {:?}", circuit);
             }
         };
@@ -65,37 +101,42 @@ fn estimate_circuit_rows() {
     }
 }
 
+#[ignore]
+#[test]
+fn estimate_row_usage_leaves_room() {
+    init();
+
+    // Chunks pre-split with the estimate keep ESTIMATE_ERROR_BOUND of their rows free, which
+    // only helps when no sub circuit needs more rows than that margin covers.
+    let (_, block_traces) = load_block_traces_for_test();
+    for block_trace in block_traces.iter() {
+        let exact = calculate_row_usage_of_trace(block_trace).unwrap();
+        let estimated = estimate_row_usage_of_trace(block_trace).unwrap();
+        for ((name, exact), estimated) in SUB_CIRCUIT_NAMES.iter().zip(&exact).zip(&estimated) {
+            log::info!("{} circuit: exact {}, estimated {}", name, exact, estimated);
+            assert!(
+                *exact as f64 * (1.0 - ESTIMATE_ERROR_BOUND) <= *estimated as f64,
+                "{name} circuit: estimated {estimated} rows, exact {exact} rows"
+            );
+        }
+    }
+}
+
 #[cfg(feature = "prove_verify")]
 #[test]
 fn test_mock_prove() {
-    use zkevm::circuit;
-
     use crate::test_util::load_block_traces_for_test;
+    use std::sync::Arc;
+    use zkevm::prover::{LogObserver, ProverObserver};
 
     init();
     let block_traces = load_block_traces_for_test().1;
+    let observer: Arc<dyn ProverObserver> = Arc::new(LogObserver);
 
     for circuit in CIRCUIT.split(",") {
-        match circuit {
-            "evm" => Prover::mock_prove_target_circuit_batch::<circuit::EvmCircuit>(&block_traces)
-                .unwrap(),
-            "state" => {
-                Prover::mock_prove_target_circuit_batch::<circuit::StateCircuit>(&block_traces)
-                    .unwrap()
-            }
-            "zktrie" => {
-                Prover::mock_prove_target_circuit_batch::<circuit::ZktrieCircuit>(&block_traces)
-                    .unwrap()
-            }
-            "poseidon" => {
-                Prover::mock_prove_target_circuit_batch::<circuit::PoseidonCircuit>(&block_traces)
-                    .unwrap()
-            }
-            "super" => {
-                Prover::mock_prove_target_circuit_batch::<circuit::SuperCircuit>(&block_traces)
-                    .unwrap()
-            }
-            _ => {
+        match target_circuit(circuit) {
+            Some(c) => c.mock_prove(&block_traces, &observer).unwrap(),
+            None => {
                 log::error!("invalid circuit, skip: {:?}", circuit);
             }
         };
@@ -105,21 +146,62 @@ fn test_mock_prove() {
 #[cfg(feature = "prove_verify")]
 #[test]
 fn test_prove_verify() {
-    use zkevm::circuit;
     for circuit in CIRCUIT.split(",") {
-        match circuit {
-            "evm" => test_target_circuit_prove_verify::<circuit::EvmCircuit>(),
-            "state" => test_target_circuit_prove_verify::<circuit::StateCircuit>(),
-            "zktrie" => test_target_circuit_prove_verify::<circuit::ZktrieCircuit>(),
-            "poseidon" => test_target_circuit_prove_verify::<circuit::PoseidonCircuit>(),
-            "super" => test_target_circuit_prove_verify::<circuit::SuperCircuit>(),
-            _ => {
+        match target_circuit(circuit) {
+            Some(c) => test_target_circuit_prove_verify(c),
+            None => {
                 log::error!("invalid circuit, skip: {:?}", circuit);
             }
         };
     }
 }
 
+/// Proves a batch of consecutive kroma blocks, read from `BATCH_TRACE_DIR`, with the
+/// SuperCircuit of a profile allowing several blocks, e.g. `CIRCUIT_PROFILE=k21-batch`, and
+/// checks the per-block public inputs against the instances of the proof.
+#[cfg(feature = "prove_verify")]
+#[test]
+fn test_prove_verify_batch() {
+    use test_util::load_batch_traces;
+    use zkevm::circuit::{validate_batch, CIRCUIT_PROFILE};
+    use zkevm::public_inputs::BlockPublicInputs;
+
+    init();
+    let batch_dir = std::env::var("BATCH_TRACE_DIR")
+        .expect("BATCH_TRACE_DIR must name a dir of consecutive kroma block traces");
+    let (_, block_traces) = load_batch_traces(&batch_dir);
+    assert!(block_traces.len() >= 2, "a batch needs at least 2 blocks");
+    assert!(
+        block_traces.len() <= CIRCUIT_PROFILE.max_inner_blocks,
+        "{} blocks, but {} proves at most {}",
+        block_traces.len(),
+        *CIRCUIT_PROFILE,
+        CIRCUIT_PROFILE.max_inner_blocks
+    );
+    validate_batch(&block_traces).unwrap();
+
+    let circuit = target_circuit(&CIRCUIT_PROFILE.super_circuit_name()).unwrap();
+    let mut prover = Prover::from_fpath_and_seed(PARAMS_DIR, SEED_PATH);
+    // The kroma traces are of chain 901.
+    prover.chain_id = 901;
+    let proof = circuit.prove(&mut prover, &block_traces).unwrap();
+    assert_eq!(proof.proved_block_count, block_traces.len());
+
+    let inputs = BlockPublicInputs::from_target_proof(&proof, &block_traces, 901).unwrap();
+    assert_eq!(inputs.blocks.len(), block_traces.len());
+    assert_eq!(inputs.prev_state_root, inputs.blocks[0].prev_state_root);
+    assert_eq!(inputs.state_root, inputs.blocks.last().unwrap().state_root);
+    for pair in inputs.blocks.windows(2) {
+        assert_eq!(pair[1].parent_hash, pair[0].hash);
+        assert_eq!(pair[1].prev_state_root, pair[0].state_root);
+    }
+    // The instances attest to the whole batch, not to a part of it.
+    assert!(BlockPublicInputs::from_target_proof(&proof, &block_traces[1..], 901).is_err());
+
+    let mut verifier = zkevm::verifier::Verifier::from_fpath(PARAMS_DIR, None);
+    circuit.verify(&mut verifier, &proof).unwrap();
+}
+
 #[cfg(feature = "prove_verify")]
 #[test]
 fn test_vk_same() {
@@ -163,7 +245,7 @@ fn test_vk_same() {
     assert_eq!(vk_empty_bytes, vk_real_bytes);
 }
 
-fn test_target_circuit_prove_verify<C: TargetCircuit>() {
+fn test_target_circuit_prove_verify(circuit: &dyn DynTargetCircuit) {
     use std::time::Instant;
 
     use zkevm::verifier::Verifier;
@@ -172,17 +254,15 @@ fn test_target_circuit_prove_verify<C: TargetCircuit>() {
 
     let (_, block_traces) = load_block_traces_for_test();
 
-    log::info!("start generating {} proof", C::name());
+    log::info!("start generating {} proof", circuit.name());
     let now = Instant::now();
-    let mut prover = Prover::from_fpath(PARAMS_DIR, SEED_PATH);
-    let proof = prover
-        .create_target_circuit_proof_batch::<C>(&block_traces)
-        .unwrap();
+    let mut prover = Prover::from_fpath_and_seed(PARAMS_DIR, SEED_PATH);
+    let proof = circuit.prove(&mut prover, &block_traces).unwrap();
     log::info!("finish generating proof, elapsed: {:?}", now.elapsed());
 
     let output_file = format!(
         "/tmp/{}_{}.json",
-        C::name(),
+        circuit.name(),
         Utc::now().format("%Y%m%d_%H%M%S")
     );
     let mut fd = std::fs::File::create(&output_file).unwrap();
@@ -192,6 +272,6 @@ fn test_target_circuit_prove_verify<C: TargetCircuit>() {
     log::info!("start verifying proof");
     let now = Instant::now();
     let mut verifier = Verifier::from_fpath(PARAMS_DIR, None);
-    assert!(verifier.verify_target_circuit_proof::<C>(&proof).is_ok());
+    assert!(circuit.verify(&mut verifier, &proof).is_ok());
     log::info!("finish verifying proof, elapsed: {:?}", now.elapsed());
 }